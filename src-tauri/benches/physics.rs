@@ -0,0 +1,54 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use sail_lib::{
+  drawing::ShipShape,
+  geometry::Vec2D,
+  physics::NetForce,
+  ship::{AdjustableShip, Ship, ShipControls, ShipSpecs},
+  simulation::Simulation
+};
+
+const WIND_ANGLE: f64 = 1.5;
+const WIND_SPEED: f64 = 5.0;
+
+/// A dinghy already moving on a reach, so every force is worked out
+fn sailing_dinghy() -> AdjustableShip {
+  let mut controls = ShipControls::new(vec![4.0], 0.1);
+  controls.crew_position = Vec2D::new(0.5, -0.3);
+  let mut ship = AdjustableShip::new(ShipSpecs::default(), Vec2D::zeros(), Vec2D::new(0.8, 0.1), 0.05, 0.2, controls);
+  ship.trim_sails(WIND_ANGLE, WIND_SPEED);
+  return ship;
+}
+
+fn forces(c: &mut Criterion) {
+  let ship = sailing_dinghy();
+  c.bench_function("forces", |b| b.iter(|| ship.forces(black_box(WIND_ANGLE), WIND_SPEED, Vec2D::zeros())));
+}
+
+/// Adding up the forces the way `update` does, without naming or keeping each one
+fn net_force(c: &mut Criterion) {
+  let ship = sailing_dinghy();
+  c.bench_function("net_force", |b| b.iter(|| {
+    let mut net = NetForce::new(ship.center_of_mass());
+    ship.add_forces(black_box(WIND_ANGLE), WIND_SPEED, Vec2D::zeros(), &mut net);
+    net
+  }));
+}
+
+fn update(c: &mut Criterion) {
+  let mut ship = sailing_dinghy();
+  c.bench_function("update", |b| b.iter(|| ship.update(black_box(WIND_ANGLE), WIND_SPEED, Vec2D::zeros(), &[])));
+}
+
+fn shape(c: &mut Criterion) {
+  let ship = sailing_dinghy();
+  c.bench_function("shape", |b| b.iter(|| ShipShape::from_adjustable(black_box(&ship))));
+}
+
+/// Drawing and serializing every ship, as the frontend asks for with `get_population`
+fn population_json(c: &mut Criterion) {
+  let sim = Simulation::new(12345, WIND_ANGLE, WIND_SPEED);
+  c.bench_function("population_json", |b| b.iter(|| serde_json::to_string(&black_box(&sim).shapes()).unwrap()));
+}
+
+criterion_group!(benches, forces, net_force, update, shape, population_json);
+criterion_main!(benches);
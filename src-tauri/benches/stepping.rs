@@ -0,0 +1,60 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rayon::ThreadPoolBuilder;
+use sail_lib::{
+  geometry::Vec2D,
+  scenario::{Scenario, ShipDefinition, ShipPreset},
+  ship::ShipControls,
+  simulation::Simulation
+};
+
+/// Dinghies on a grid ten metres apart, all sailing with their sheets eased
+fn fleet(size: usize) -> Simulation {
+  let mut scenario = Scenario::sandbox(12345, 0.0, 5.0);
+  let columns = (size as f64).sqrt().ceil() as usize;
+  scenario.ships = (0..size).map(|i| ShipDefinition {
+    preset: ShipPreset::Dinghy,
+    loc: Vec2D::new((i % columns) as f64 * 10.0, (i / columns) as f64 * 10.0),
+    heading: i as f64 * 0.1,
+    controller: None,
+  }).collect();
+  let mut sim = Simulation::from_scenario(scenario).unwrap();
+  for i in 0..size {
+    sim.update_ship_controls(i, ShipControls::new(vec![4.0], 0.1));
+  }
+  return sim;
+}
+
+/// Stepping populations from a few ships, updated one after another, to thousands updated in parallel
+///
+/// Every step is taken from a copy of the same fresh fleet, so the ships don't drift apart or collide over a long
+/// measurement and change what is being measured.
+fn step(c: &mut Criterion) {
+  let mut group = c.benchmark_group("step");
+  for size in [1, 16, 256, 4096] {
+    group.throughput(Throughput::Elements(size as u64));
+    let start = fleet(size);
+    group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+      b.iter_batched_ref(|| start.clone(), |sim| sim.step(), BatchSize::LargeInput)
+    });
+  }
+  group.finish();
+}
+
+/// How stepping scales with the size of the population and the number of threads
+fn parallel_step(c: &mut Criterion) {
+  let mut group = c.benchmark_group("parallel_step");
+  for size in [64, 256, 1024, 4096] {
+    group.throughput(Throughput::Elements(size as u64));
+    for threads in [1, 2, 4, 8] {
+      let pool = ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+      let start = fleet(size);
+      group.bench_with_input(BenchmarkId::new(format!("{}_threads", threads), size), &size, |b, _| {
+        b.iter_batched_ref(|| start.clone(), |sim| pool.install(|| sim.step()), BatchSize::LargeInput)
+      });
+    }
+  }
+  group.finish();
+}
+
+criterion_group!(benches, step, parallel_step);
+criterion_main!(benches);
@@ -0,0 +1,204 @@
+use std::f64::consts::PI;
+
+use serde::Serialize;
+
+use crate::{
+  drawing::ShipShape,
+  geometry::{bound, Vec2D},
+  physics::{calculate_aero_force_vecs, calculate_force, Force, ForceName, ForceSink, HullSide, Kinematics},
+  ship::{
+    calculate_hull_mass, calculate_rig_mass, HullSpecs, SailSpecs, SailingShip, ShipControls,
+    CREW_MASS, DENSITY_WATER, DENSITY_WOOD, GRAVITY, HULL_FRICTION_COEFFICIENT
+  },
+  simulation::DELTA_TIME
+};
+
+pub const CROSSBEAM_SIZE: f64 = 0.1; // m, square section of each crossbeam
+
+/// The two hulls, from the port side to the starboard side
+const HULL_SIDES: [(f64, HullSide); 2] = [(1.0, HullSide::Port), (-1.0, HullSide::Starboard)];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CatamaranSpecs {
+  pub hull_width: f64,
+  pub hull_length: f64,
+  pub hull_depth: f64,
+  pub hull_thickness: f64,
+  /// Distance between the centerlines of the two hulls
+  pub hull_spacing: f64,
+  pub daggerboard_offset: f64,
+  pub daggerboard_length: f64,
+  pub daggerboard_height: f64,
+  pub rudder_length: f64,
+  pub rudder_height: f64,
+  pub sails: Vec<SailSpecs>,
+}
+impl CatamaranSpecs {
+  pub fn new(hull_width: f64, hull_length: f64, hull_depth: f64, hull_thickness: f64, hull_spacing: f64,
+    daggerboard_offset: f64, daggerboard_length: f64, daggerboard_height: f64,
+    rudder_length: f64, rudder_height: f64, sails: Vec<SailSpecs>) -> Self {
+    Self {
+      hull_width, hull_length, hull_depth, hull_thickness, hull_spacing,
+      daggerboard_offset, daggerboard_length, daggerboard_height,
+      rudder_length, rudder_height, sails
+    }
+  }
+
+  /// Lift and drag of a foil fixed to a hull at the given offset, angled relative to the hull, with the ship's motion
+  /// given through the water rather than over the ground
+  fn add_foil_forces(&self, kinematics: Kinematics, names: (ForceName, ForceName), offset: Vec2D, angle: f64, area: f64, sink: &mut dyn ForceSink) {
+    let water_vel = kinematics.point_velocity(offset).scale(-DELTA_TIME);
+    let aoa: f64 = bound(kinematics.heading + angle - water_vel.to_angle(), 0.0, PI);
+    let (lift, drag) = calculate_aero_force_vecs(aoa, area, DENSITY_WATER, water_vel);
+    let loc = kinematics.point(offset);
+    sink.add(names.0, loc, lift);
+    sink.add(names.1, loc, drag);
+  }
+
+  /// Skin friction on one end of a hull, sampled a quarter of the way along it, with the ship's motion given through the
+  /// water
+  fn hull_drag(&self, kinematics: Kinematics, name: ForceName, side_offset: f64, direction: f64, depth: f64) -> Force {
+    let sample_offset = Vec2D::new(direction * self.hull_length * 0.25, side_offset);
+    let water_vel = kinematics.point_velocity(sample_offset).scale(-DELTA_TIME);
+    let aoa: f64 = bound(kinematics.heading - water_vel.to_angle(), 0.0, PI);
+    let apparent_width = f64::cos(aoa).abs() * self.hull_width + f64::sin(aoa).abs() * self.hull_length * 0.5;
+    let wetted_area = depth * apparent_width;
+    let drag_magnitude = calculate_force(HULL_FRICTION_COEFFICIENT, wetted_area, DENSITY_WATER, water_vel.magnitude());
+    let drag = water_vel.unit().scale(drag_magnitude);
+    let loc = kinematics.point(Vec2D::new(direction * self.hull_length * 0.5, side_offset));
+    return Force::new(name, loc, drag);
+  }
+}
+impl Default for CatamaranSpecs {
+  fn default() -> Self {
+    Self::new(
+      1.0, 10.0, 0.5, 0.05, 4.0,
+      0.5, 0.5, 1.5,
+      0.75, 1.0, vec![SailSpecs::new(3.0, 6.0, 12.0)]
+    )
+  }
+}
+impl HullSpecs for CatamaranSpecs {
+  fn sails(&self) -> &[SailSpecs] {
+    &self.sails
+  }
+
+  fn calculate_mass(&self) -> f64 {
+    let mass_hulls = 2.0 * calculate_hull_mass(self.hull_length, self.hull_width, self.hull_depth, self.hull_thickness);
+    let mass_crossbeams = 2.0 * DENSITY_WOOD * self.hull_spacing * CROSSBEAM_SIZE * CROSSBEAM_SIZE;
+    return mass_hulls + mass_crossbeams + calculate_rig_mass(&self.sails);
+  }
+
+  fn length(&self) -> f64 {
+    self.hull_length
+  }
+
+  /// Outside edge to outside edge, with the travellers running the full width of the crossbeams
+  fn beam(&self) -> f64 {
+    self.hull_spacing + self.hull_width
+  }
+
+  fn add_water_forces(&self, kinematics: Kinematics, controls: &ShipControls, total_mass: f64, current: Vec2D, sink: &mut dyn ForceSink) {
+    // The crew's weight trims the bows or sterns down and presses one hull deeper than the other
+    let crew_share = CREW_MASS / total_mass;
+    let trim = (controls.crew_position.x / (self.hull_length * 0.5)) * crew_share;
+    let half_spacing = self.hull_spacing * 0.5;
+    let through_water = Kinematics { vel: kinematics.vel - current, ..kinematics };
+
+    for (side, hull) in HULL_SIDES {
+      let side_offset = side * half_spacing;
+      let heel = (controls.crew_position.y / half_spacing) * side * crew_share;
+      let depth = self.hull_depth * (1.0 + heel);
+
+      let daggerboard_center = self.daggerboard_offset - self.daggerboard_length * 0.5;
+      self.add_foil_forces(
+        through_water,
+        (ForceName::DaggerboardLift(hull), ForceName::DaggerboardDrag(hull)),
+        Vec2D::new(daggerboard_center, side_offset),
+        0.0,
+        self.daggerboard_length * self.daggerboard_height,
+        sink
+      );
+
+      self.add_foil_forces(
+        through_water,
+        (ForceName::HullRudderLift(hull), ForceName::HullRudderDrag(hull)),
+        Vec2D::new(-self.hull_length * 0.5, side_offset),
+        controls.rudder_angle,
+        self.rudder_length * self.rudder_height,
+        sink
+      );
+
+      sink.add_force(self.hull_drag(through_water, ForceName::HullBowDrag(hull), side_offset, 1.0, depth * (1.0 + trim)));
+      sink.add_force(self.hull_drag(through_water, ForceName::HullSternDrag(hull), side_offset, -1.0, depth * (1.0 - trim)));
+    }
+  }
+
+  fn shape(ship: &Catamaran) -> ShipShape {
+    ShipShape::from_catamaran(ship)
+  }
+
+  fn deck_height(&self) -> f64 {
+    self.hull_depth * 0.5
+  }
+
+  /// Until the windward hull lifts clear, the whole weight of the boat pivots about the leeward hull
+  fn righting_moment(&self, total_mass: f64) -> f64 {
+    total_mass * GRAVITY * self.hull_spacing * 0.5
+  }
+}
+
+/// A sailboat with two narrow hulls joined by crossbeams, steered by a rudder on each hull
+pub type Catamaran = SailingShip<CatamaranSpecs>;
+
+#[cfg(test)]
+mod tests {
+  use std::f64::consts::PI;
+  use super::{Catamaran, CatamaranSpecs};
+  use crate::{
+    geometry::Vec2D,
+    physics::{ForceName, HullSide},
+    ship::{AdjustableShip, Ship, ShipControls, ShipSpecs}
+  };
+
+  /// How far the ship has turned after ten seconds on a beam reach with the helm left amidships, wind from the north
+  fn beam_reach(mut ship: Box<dyn Ship>) -> f64 {
+    for _ in 0..300 {
+      ship.update(PI * 0.5, 5.0, Vec2D::zeros(), &[]);
+    }
+    return ship.kinematics().heading;
+  }
+
+  #[test]
+  fn test_holds_course_better_than_a_dinghy() {
+    let controls = ShipControls::new(vec![1.0], 0.0);
+    let catamaran = Catamaran::new(CatamaranSpecs::default(), Vec2D::zeros(), Vec2D::new(3.0, 0.0), 0.0, 0.0, controls.clone());
+    let dinghy = AdjustableShip::new(ShipSpecs::default(), Vec2D::zeros(), Vec2D::new(3.0, 0.0), 0.0, 0.0, controls);
+
+    // The water pushes on a daggerboard and rudder under each hull rather than a single keel
+    let names: Vec<ForceName> = catamaran.forces(PI * 0.5, 5.0, Vec2D::zeros()).iter().map(|force| force.name).collect();
+    assert!(names.contains(&ForceName::DaggerboardLift(HullSide::Port)));
+    assert!(names.contains(&ForceName::HullRudderLift(HullSide::Starboard)));
+    assert!(!names.contains(&ForceName::ForeKeelLift));
+
+    // Both bear away from the wind, but the catamaran turns much less
+    let catamaran_turn = beam_reach(Box::new(catamaran)).abs();
+    let dinghy_turn = beam_reach(Box::new(dinghy)).abs();
+    assert!(catamaran_turn > 0.0);
+    assert!(catamaran_turn < dinghy_turn * 0.75, "{} {}", catamaran_turn, dinghy_turn);
+  }
+
+  #[test]
+  fn test_heels_away_from_the_wind() {
+    let mut catamaran = Catamaran::new(CatamaranSpecs::default(), Vec2D::zeros(), Vec2D::new(3.0, 0.0), 0.0, 0.0, ShipControls::new(vec![1.0], 0.0));
+    catamaran.trim_sails(PI * 0.5, 5.0);
+    let heel = catamaran.heel(PI * 0.5, 5.0);
+    assert!(heel > 0.0);
+
+    // Sitting out on the windward hull brings it back upright
+    let mut controls = catamaran.controls.clone();
+    controls.crew_position = Vec2D::new(0.0, 2.5);
+    catamaran.set_controls(controls);
+    assert!(catamaran.heel(PI * 0.5, 5.0) < heel);
+  }
+}
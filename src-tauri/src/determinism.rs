@@ -0,0 +1,380 @@
+use std::{fs, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::{
+  geometry::Vec2D,
+  instruments::InstrumentsConfig,
+  scenario::{Scenario, ShipPreset},
+  ship::ShipControls,
+  simulation::{SimSettings, Simulation},
+  towing::ShipLine
+};
+
+pub const CHECKPOINT_INTERVAL: u64 = 30; // Steps between state hashes, once a second
+
+/// Something done to the simulation from outside, which has to be done again at the same step to reproduce a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Input {
+  Controls { ship: usize, controls: ShipControls },
+  Throttle { ship: usize, throttle: f64 },
+  Settings(SimSettings),
+  DropAnchor { ship: usize, rode_length: f64 },
+  WeighAnchor { ship: usize },
+  MoorShip { ship: usize, mooring_point: Vec2D, line_length: f64 },
+  CastOff { ship: usize },
+  AddLine(ShipLine),
+  ReleaseLines { ship: usize },
+  Instruments { ship: usize, config: Option<InstrumentsConfig> },
+  AddShip { preset: ShipPreset, loc: Vec2D, heading: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputEntry {
+  /// The step the simulation was on when the input was given, before the next step was taken
+  pub step: u64,
+  pub input: Input,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+  pub step: u64,
+  pub hash: u64,
+}
+
+/// How far a log had got, so it can be put back to that point without keeping a copy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogCursor {
+  inputs: usize,
+  checkpoints: usize,
+  interrupted: bool,
+}
+
+/// Everything needed to run the simulation again from the start and check it ends up the same
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputLog {
+  pub seed: u64,
+  /// Settings the simulation started with
+  pub settings: SimSettings,
+  /// The scenario the simulation started from, or the sandbox if not given
+  #[serde(default)]
+  pub scenario: Option<Scenario>,
+  pub inputs: Vec<InputEntry>,
+  pub checkpoints: Vec<Checkpoint>,
+  /// Whether the run was changed in a way the log can't reproduce, such as restoring a recorded frame
+  pub interrupted: bool,
+}
+impl InputLog {
+  pub fn new(scenario: &Scenario) -> Self {
+    Self {
+      seed: scenario.seed,
+      settings: scenario.start_settings(),
+      scenario: Some(scenario.clone()),
+      inputs: Vec::new(),
+      checkpoints: Vec::new(),
+      interrupted: false,
+    }
+  }
+
+  pub fn record(&mut self, step: u64, input: Input) {
+    self.inputs.push(InputEntry { step, input });
+  }
+
+  pub fn checkpoint(&mut self, step: u64, hash: u64) {
+    self.checkpoints.push(Checkpoint { step, hash });
+  }
+
+  pub fn interrupt(&mut self) {
+    self.interrupted = true;
+  }
+
+  pub fn cursor(&self) -> LogCursor {
+    LogCursor { inputs: self.inputs.len(), checkpoints: self.checkpoints.len(), interrupted: self.interrupted }
+  }
+
+  /// Forget everything logged since the cursor was taken
+  pub fn rewind(&mut self, cursor: LogCursor) {
+    self.inputs.truncate(cursor.inputs);
+    self.checkpoints.truncate(cursor.checkpoints);
+    self.interrupted = cursor.interrupted;
+  }
+
+  /// The last step anything was logged at
+  pub fn last_step(&self) -> u64 {
+    let last_input = self.inputs.last().map_or(0, |entry| entry.step);
+    let last_checkpoint = self.checkpoints.last().map_or(0, |checkpoint| checkpoint.step);
+    return u64::max(last_input, last_checkpoint);
+  }
+}
+
+/// Hash of exact floating point values, so any difference in the state at all shows up
+///
+/// This is FNV-1a rather than the standard library's hasher, whose output may change between Rust versions.
+pub struct StateHasher {
+  hash: u64,
+}
+impl StateHasher {
+  pub fn new() -> Self {
+    Self { hash: 0xcbf29ce484222325 }
+  }
+
+  pub fn write_u64(&mut self, value: u64) {
+    for byte in value.to_le_bytes() {
+      self.hash ^= byte as u64;
+      self.hash = self.hash.wrapping_mul(0x100000001b3);
+    }
+  }
+
+  pub fn write_f64(&mut self, value: f64) {
+    self.write_u64(value.to_bits());
+  }
+
+  pub fn write_vec(&mut self, value: Vec2D) {
+    self.write_f64(value.x);
+    self.write_f64(value.y);
+  }
+
+  pub fn finish(&self) -> u64 {
+    self.hash
+  }
+}
+impl Default for StateHasher {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// The outcome of running a log again
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct VerifyReport {
+  pub steps: u64,
+  pub checkpoints: usize,
+  /// The first step the state differed from the log, if any did
+  pub mismatch: Option<u64>,
+}
+
+/// Run the simulation again from the seed, giving the logged inputs at the same steps, until the last thing logged or
+/// until `after_step` returns false, returning the simulation as it was left
+pub fn replay(log: &InputLog, mut after_step: impl FnMut(&Simulation) -> bool) -> Result<Simulation, String> {
+  if log.interrupted {
+    return Result::Err(String::from("The run was interrupted by restoring a recording or changing the ships, so it can't be reproduced"));
+  }
+  // Whatever the scenario's scripts and changes of wind did was logged, so only its starting state is needed
+  let mut sim = match &log.scenario {
+    Some(scenario) => Simulation::from_scenario(scenario.starting_state())?,
+    None => Simulation::new(log.seed, log.settings.wind_angle, log.settings.wind_speed),
+  };
+  let mut inputs = log.inputs.iter().peekable();
+  let last_step = log.last_step();
+  loop {
+    while let Some(entry) = inputs.next_if(|entry| entry.step == sim.get_step()) {
+      sim.apply(&entry.input).map_err(|e| format!("Input at step {}: {}", entry.step, e))?;
+    }
+    if sim.get_step() >= last_step {
+      break;
+    }
+    sim.step();
+    if !after_step(&sim) {
+      break;
+    }
+  }
+  return Result::Ok(sim);
+}
+
+/// Replay the log and compare every checkpoint
+pub fn verify(log: &InputLog) -> Result<VerifyReport, String> {
+  let mut checkpoints = log.checkpoints.iter().peekable();
+  let mut report = VerifyReport { steps: 0, checkpoints: 0, mismatch: None };
+  replay(log, |sim| {
+    report.steps += 1;
+    if let Some(checkpoint) = checkpoints.next_if(|checkpoint| checkpoint.step == sim.get_step()) {
+      report.checkpoints += 1;
+      if checkpoint.hash != sim.state_hash() {
+        report.mismatch = Some(checkpoint.step);
+        return false;
+      }
+    }
+    return true;
+  })?;
+  return Result::Ok(report);
+}
+
+/// Read a log saved to a file
+pub fn load_log(path: &str) -> Result<InputLog, String> {
+  let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+  return serde_json::from_str(&json).map_err(|e| e.to_string());
+}
+
+/// Verify a log saved to a file, for `--verify <path>` on the command line
+pub fn verify_file(path: &str) -> Result<VerifyReport, String> {
+  return verify(&load_log(path)?);
+}
+
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn save_input_log(sim: State<Mutex<Simulation>>, path: String) -> Result<(), String> {
+  let sim = sim.lock().unwrap();
+  let json = serde_json::to_string(sim.get_log()).map_err(|e| e.to_string())?;
+  return fs::write(path, json).map_err(|e| e.to_string());
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn verify_input_log(path: String) -> Result<VerifyReport, String> {
+  return verify_file(&path);
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::f64::consts::PI;
+  use super::{verify, Input, InputLog};
+  use crate::{
+    geometry::Vec2D,
+    scenario::ShipPreset,
+    ship::ShipControls,
+    simulation::Simulation
+  };
+
+  const SEED: u64 = 12345;
+
+  /// A run exercising sails, engines, anchors, lines and changes of wind
+  fn scripted_run(steps: u64) -> Simulation {
+    let mut sim = Simulation::new(SEED, 0.0, 5.0);
+    for step in 0..steps {
+      match step {
+        0 => {
+          sim.update_ship_controls(0, ShipControls::new(vec![4.0], 0.2));
+          sim.update_ship_throttle(4, 0.8);
+        }
+        60 => sim.set_settings(PI / 4.0, 3.0),
+        90 => sim.drop_anchor(1, 10.0).unwrap(),
+        120 => sim.tow_ship(4, 2, 12.0).unwrap(),
+        200 => {
+          let mut controls = ShipControls::new(vec![6.0], -0.3);
+          controls.reef_amounts = vec![0.5];
+          controls.crew_position = Vec2D::new(1.0, -0.5);
+          sim.update_ship_controls(3, controls);
+        }
+        260 => sim.moor_ship(0, Vec2D::new(80.0, 20.0), 15.0).unwrap(),
+        300 => sim.release_lines(4),
+        330 => sim.weigh_anchor(1),
+        _ => {}
+      }
+      sim.step();
+    }
+    return sim;
+  }
+
+  #[test]
+  fn test_same_inputs_same_state() {
+    let a = scripted_run(450);
+    let b = scripted_run(450);
+    assert_eq!(a.get_log().checkpoints, b.get_log().checkpoints);
+    assert_eq!(a.state_hash(), b.state_hash());
+    assert_eq!(a.ship_states(), b.ship_states());
+  }
+
+  #[test]
+  fn test_ships_move() {
+    // A run where nothing moves would verify trivially
+    let sim = scripted_run(450);
+    let start = Simulation::new(SEED, 0.0, 5.0);
+    for (before, after) in start.ship_states().iter().zip(sim.ship_states().iter()) {
+      assert_ne!(before.kinematics.loc, after.kinematics.loc);
+    }
+  }
+
+  #[test]
+  fn test_verify_log() {
+    let sim = scripted_run(450);
+    let report = verify(sim.get_log()).unwrap();
+    assert_eq!(report.mismatch, None);
+    assert_eq!(report.checkpoints, sim.get_log().checkpoints.len());
+    assert_eq!(report.steps, 450);
+  }
+
+  #[test]
+  fn test_verify_saved_log() {
+    let sim = scripted_run(450);
+    let json = serde_json::to_string(sim.get_log()).unwrap();
+    let log: InputLog = serde_json::from_str(&json).unwrap();
+    assert_eq!(verify(&log).unwrap().mismatch, None);
+  }
+
+  #[test]
+  fn test_verify_detects_changed_input() {
+    let sim = scripted_run(450);
+    let mut log = sim.get_log().clone();
+    let entry = log.inputs.iter_mut().find(|entry| matches!(entry.input, Input::Throttle { .. })).unwrap();
+    entry.input = Input::Throttle { ship: 4, throttle: 0.7 };
+    let report = verify(&log).unwrap();
+    assert_eq!(report.mismatch, Some(log.checkpoints[0].step));
+  }
+
+  #[test]
+  fn test_verify_after_snapshot_restore() {
+    let mut sim = scripted_run(150);
+    let snapshot = sim.save_state();
+    sim.run(100, None, |_| {});
+    sim.restore_state(&snapshot);
+    sim.update_ship_controls(1, ShipControls::new(vec![2.0], 0.1));
+    sim.run(100, None, |_| {});
+    assert_eq!(verify(sim.get_log()).unwrap().mismatch, None);
+  }
+
+  #[test]
+  fn test_verify_spawned_ship() {
+    let mut sim = scripted_run(100);
+    let index = sim.spawn_ship(ShipPreset::MotorBoat, Vec2D::new(20.0, -20.0), 1.0);
+    sim.update_ship_throttle(index, 1.0);
+    sim.run(140, None, |_| {});
+    assert!(!sim.get_log().interrupted);
+    let report = verify(sim.get_log()).unwrap();
+    assert_eq!(report.mismatch, None);
+    assert_eq!(report.steps, 240);
+  }
+
+  #[test]
+  fn test_verify_rejects_impossible_input() {
+    let sim = scripted_run(150);
+    let mut log = sim.get_log().clone();
+    let entry = log.inputs.iter_mut().find(|entry| matches!(entry.input, Input::AddLine(_))).unwrap();
+    if let Input::AddLine(line) = &mut entry.input {
+      line.to = 99;
+    }
+    assert!(verify(&log).is_err());
+  }
+
+  #[test]
+  fn test_hash_covers_controls_and_settings() {
+    let sim = scripted_run(60);
+    let mut controls = sim.clone();
+    let mut trimmed = sim.get_ship(0).unwrap().controls().clone();
+    trimmed.vang_tensions = vec![0.5];
+    controls.update_ship_controls(0, trimmed);
+    assert_ne!(controls.state_hash(), sim.state_hash());
+    let mut settings = sim.clone();
+    settings.set_current(Vec2D::new(0.1, 0.0));
+    assert_ne!(settings.state_hash(), sim.state_hash());
+  }
+
+  #[test]
+  fn test_restoring_states_interrupts_log() {
+    let mut sim = scripted_run(100);
+    let states = sim.ship_states();
+    sim.run(10, None, |_| {});
+    sim.restore_ship_states(100, &states).unwrap();
+    assert!(verify(sim.get_log()).is_err());
+  }
+
+  #[test]
+  fn test_reset_starts_new_log() {
+    let mut sim = scripted_run(200);
+    sim.reset();
+    sim.update_ship_throttle(4, -0.5);
+    sim.run(200, None, |_| {});
+    assert_eq!(sim.get_log().inputs.len(), 1);
+    assert_eq!(verify(sim.get_log()).unwrap().mismatch, None);
+  }
+}
@@ -1,21 +1,30 @@
 use serde::Serialize;
 
 use crate::{
-  geometry::Vec2D, physics::Force, ship::AdjustableShip
+  catamaran::Catamaran, geometry::{invert_angle, Vec2D}, ghost::{GhostShip, GHOST_LENGTH, GHOST_WIDTH}, physics::Force, ship::AdjustableShip
 };
 
+// Line drawing constants
+const ROPE_THICKNESS: f64 = 0.2;
+
 // Ship drawing constants
 const LINE_THICKNESS: f64 = 0.5;
 
-#[derive(Debug, Clone, Serialize)]
-pub struct AdjustableShipShape {
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShipShape {
   center: Vec2D,
-  hull: Polygon,
+  hulls: Vec<Polygon>,
   sails: Vec<Polygon>,
-  rudder: Polygon,
+  rudders: Vec<Polygon>,
+  /// Whether the ship replays a recorded track rather than being simulated
+  ghost: bool,
 }
-impl AdjustableShipShape {
-  pub fn new(ship: &AdjustableShip) -> Self {
+impl ShipShape {
+  pub fn new(center: Vec2D, hulls: Vec<Polygon>, sails: Vec<Polygon>, rudders: Vec<Polygon>) -> Self {
+    Self { center, hulls, sails, rudders, ghost: false }
+  }
+
+  pub fn from_adjustable(ship: &AdjustableShip) -> Self {
     let hull = Polygon::centered_rectangle(
       ship.specs.hull_length, ship.specs.hull_width,
       ship.heading, ship.loc
@@ -23,7 +32,7 @@ impl AdjustableShipShape {
 
     let mut sails: Vec<Polygon> = Vec::new();
     for sail_index in 0..ship.specs.sails.len() {
-      let sail = &ship.specs.sails[sail_index];
+      let sail = ship.effective_sail(sail_index);
       sails.push(Polygon::line(
         sail.width, LINE_THICKNESS,
         ship.sail_angles[sail_index], Vec2D::at_x(sail.mast_offset),
@@ -33,16 +42,51 @@ impl AdjustableShipShape {
 
     let rudder = Polygon::line(
       ship.specs.rudder_length, LINE_THICKNESS,
-      ship.rudder_angle, Vec2D::at_x(-ship.specs.hull_length*0.5),
+      ship.controls.rudder_angle, Vec2D::at_x(-ship.specs.hull_length*0.5),
       ship.heading, ship.loc
     );
 
-    Self {
-      center: ship.loc,
-      hull,
-      sails,
-      rudder
+    Self::new(ship.loc, vec![hull], sails, vec![rudder])
+  }
+
+  pub fn from_catamaran(ship: &Catamaran) -> Self {
+    let half_spacing = ship.specs.hull_spacing * 0.5;
+    let mut hulls: Vec<Polygon> = Vec::new();
+    let mut rudders: Vec<Polygon> = Vec::new();
+    // Trampoline stretched between the hulls
+    hulls.push(Polygon::centered_rectangle(
+      ship.specs.hull_length * 0.5, ship.specs.hull_spacing,
+      ship.heading, ship.loc
+    ));
+    for side in [1.0, -1.0] {
+      let hull_center = Vec2D::at_y(side * half_spacing);
+      hulls.push(Polygon::centered_rectangle(
+        ship.specs.hull_length, ship.specs.hull_width,
+        ship.heading, hull_center.transform(ship.heading, ship.loc)
+      ));
+      rudders.push(Polygon::line(
+        ship.specs.rudder_length, LINE_THICKNESS,
+        ship.controls.rudder_angle, Vec2D::new(-ship.specs.hull_length*0.5, side * half_spacing),
+        ship.heading, ship.loc
+      ));
     }
+
+    let mut sails: Vec<Polygon> = Vec::new();
+    for sail_index in 0..ship.specs.sails.len() {
+      let sail = ship.effective_sail(sail_index);
+      sails.push(Polygon::line(
+        sail.width, LINE_THICKNESS,
+        ship.sail_angles[sail_index], Vec2D::at_x(sail.mast_offset),
+        ship.heading, ship.loc
+      ));
+    }
+
+    Self::new(ship.loc, hulls, sails, rudders)
+  }
+
+  pub fn from_ghost(ship: &GhostShip) -> Self {
+    let hull = Polygon::centered_rectangle(GHOST_LENGTH, GHOST_WIDTH, ship.heading, ship.loc);
+    Self { ghost: true, ..Self::new(ship.loc, vec![hull], Vec::new(), Vec::new()) }
   }
 }
 
@@ -53,7 +97,7 @@ impl Vec2D {
   }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Polygon {
   points: Vec<Vec2D>
 }
@@ -69,6 +113,11 @@ impl Polygon {
     points.push(Vec2D::new(-half_length, -half_width).transform(heading, location));
     Self { points }
   }
+  /// Create a line between two points with the given thickness
+  pub fn segment(start: Vec2D, end: Vec2D, thickness: f64) -> Self {
+    let direction = end - start;
+    Self::line(direction.magnitude(), thickness, 0.0, Vec2D::zeros(), invert_angle(direction.to_angle()), start)
+  }
   /// Create a line from (0,0) to (-length,0) with given thickness, moved to the transformed location
   pub fn line(length: f64, thickness: f64, angle: f64, offset: Vec2D, heading: f64, location: Vec2D) -> Self {
     let half_thickness = thickness * 0.5;
@@ -100,12 +149,17 @@ impl Arrow {
     }
   }
   pub fn from_force(force: &Force) -> Self {
-    Self::new(force.name.clone(), force.loc, force.loc + force.vec, LINE_THICKNESS, 1.0)
+    Self::new(force.name.to_string(), force.loc, force.loc + force.vec, LINE_THICKNESS, 1.0)
   }
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct PhysicsShapes {
-  pub ship: AdjustableShipShape,
+  pub ship: ShipShape,
   pub forces: Vec<Arrow>
 }
+
+/// Draw a rope between the two points it is made fast to
+pub fn rope(start: Vec2D, end: Vec2D) -> Polygon {
+  Polygon::segment(start, end, ROPE_THICKNESS)
+}
@@ -0,0 +1,288 @@
+use std::{
+  sync::Mutex,
+  time::{Duration, Instant}
+};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::{
+  drawing::{Polygon, ShipShape},
+  geometry::Vec2D,
+  scripting::ScriptError,
+  recording::Recorder,
+  snapshots::Snapshots,
+  simulation::Simulation
+};
+
+pub const DEFAULT_EVENT_RATE: f64 = 30.0; // Updates per second
+
+/// Something that happened in the simulation, rather than a change to its ongoing state
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum SimEvent {
+  LineParted { from: usize, to: usize },
+  /// The hulls of two ships have just come into contact
+  Collision { a: usize, b: usize },
+  /// A ship has come within reach of the next mark of the course
+  MarkReached { ship: usize, mark: usize },
+  /// A ship has reached every mark of the course in order
+  CourseFinished { ship: usize },
+  ScriptError(ScriptError),
+  /// The scenario has run for as long as it lasts
+  ScenarioFinished,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangedShape {
+  pub index: usize,
+  pub shape: ShipShape,
+}
+
+/// The ships that have moved since the last update
+#[derive(Debug, Clone, Serialize)]
+pub struct PopulationUpdate {
+  pub step: u64,
+  /// Size of the whole population, so removed ships can be dropped
+  pub count: usize,
+  pub changed: Vec<ChangedShape>,
+  /// Every line between ships, if any of them moved
+  pub lines: Option<Vec<Polygon>>,
+  /// Every course mark, if any of them changed
+  pub marks: Option<Vec<Vec2D>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectedShipState {
+  pub index: usize,
+  pub ship: serde_json::Value,
+}
+
+/// Everything to send to the frontend in one update
+pub struct Updates {
+  population: Option<PopulationUpdate>,
+  selected: Option<SelectedShipState>,
+  events: Vec<SimEvent>,
+}
+
+/// Tracks what the frontend was last sent, so that only changes are sent at a limited rate
+pub struct Publisher {
+  interval: Duration,
+  last_publish: Option<Instant>,
+  selected: Option<usize>,
+  sent_shapes: Vec<ShipShape>,
+  sent_lines: Vec<Polygon>,
+  sent_marks: Vec<Vec2D>,
+  sent_selected: Option<serde_json::Value>,
+}
+impl Publisher {
+  pub fn new(rate: f64) -> Self {
+    Self {
+      interval: Duration::from_secs_f64(1.0 / rate),
+      last_publish: None,
+      selected: None,
+      sent_shapes: Vec::new(),
+      sent_lines: Vec::new(),
+      sent_marks: Vec::new(),
+      sent_selected: None,
+    }
+  }
+
+  pub fn selected(&self) -> Option<usize> {
+    self.selected
+  }
+
+  /// Whether enough time has passed since the last update to send another
+  pub fn due(&self) -> bool {
+    self.last_publish.map_or(true, |last| last.elapsed() >= self.interval)
+  }
+
+  /// Gather everything that has changed since the last update
+  pub fn collect(&mut self, sim: &mut Simulation) -> Updates {
+    self.last_publish = Some(Instant::now());
+
+    let population = sim.get_population();
+    self.sent_shapes.truncate(population.len());
+    let mut changed = Vec::new();
+    for (index, ship) in population.iter().enumerate() {
+      let shape = ship.shape();
+      match self.sent_shapes.get_mut(index) {
+        Some(sent) if *sent == shape => {}
+        Some(sent) => {
+          *sent = shape.clone();
+          changed.push(ChangedShape { index, shape });
+        }
+        None => {
+          self.sent_shapes.push(shape.clone());
+          changed.push(ChangedShape { index, shape });
+        }
+      }
+    }
+    let line_shapes = sim.line_shapes();
+    let lines = match line_shapes == self.sent_lines {
+      true => None,
+      false => {
+        self.sent_lines = line_shapes.clone();
+        Some(line_shapes)
+      }
+    };
+    let marks = match *sim.get_marks() == self.sent_marks {
+      true => None,
+      false => {
+        self.sent_marks = sim.get_marks().clone();
+        Some(self.sent_marks.clone())
+      }
+    };
+    let population_update = match changed.is_empty() && lines.is_none() && marks.is_none() {
+      true => None,
+      false => Some(PopulationUpdate { step: sim.get_step(), count: population.len(), changed, lines, marks }),
+    };
+
+    let selected_state = self.selected
+      .and_then(|index| sim.get_ship(index).map(|ship| (index, ship.to_json())));
+    let selected_update = match selected_state {
+      Some((index, ship)) if self.sent_selected.as_ref() != Some(&ship) => {
+        self.sent_selected = Some(ship.clone());
+        Some(SelectedShipState { index, ship })
+      }
+      _ => None,
+    };
+
+    return Updates { population: population_update, selected: selected_update, events: sim.drain_events() };
+  }
+}
+
+/// Send one update to the frontend, logging it rather than stopping the simulation if it can't be sent
+fn emit_event<T: Serialize + Clone>(app: &AppHandle, event: &str, payload: T) {
+  if let Err(error) = app.emit_all(event, payload) {
+    eprintln!("Couldn't send {}: {}", event, error);
+  }
+}
+
+/// Send the updates to the frontend
+pub fn emit(app: &AppHandle, updates: Updates) {
+  if let Some(population) = updates.population {
+    emit_event(app, "population-updated", population);
+  }
+  if let Some(selected) = updates.selected {
+    emit_event(app, "ship-selected-state", selected);
+  }
+  for event in updates.events {
+    emit_event(app, "simulation-event", event);
+  }
+}
+
+/// Step the simulation once and send any updates that are due
+pub fn step_and_publish(app: &AppHandle, force: bool) -> u64 {
+  let (step, updates) = {
+    let sim = app.state::<Mutex<Simulation>>();
+    let mut sim = sim.lock().unwrap();
+    sim.step();
+    let recorder = app.state::<Mutex<Recorder>>();
+    let mut recorder = recorder.lock().unwrap();
+    // Stepping carries on from whatever was being replayed
+    recorder.stop_replay();
+    recorder.record(&sim);
+    app.state::<Mutex<Snapshots>>().lock().unwrap().auto_snapshot(&sim);
+    let publisher = app.state::<Mutex<Publisher>>();
+    let mut publisher = publisher.lock().unwrap();
+    let updates = match force || publisher.due() {
+      true => Some(publisher.collect(&mut sim)),
+      false => None,
+    };
+    (sim.get_step(), updates)
+  };
+  if let Some(updates) = updates {
+    emit(app, updates);
+  }
+  return step;
+}
+
+/// Send everything that has changed straight away
+pub fn publish(app: &AppHandle) {
+  let updates = {
+    let sim = app.state::<Mutex<Simulation>>();
+    let mut sim = sim.lock().unwrap();
+    let publisher = app.state::<Mutex<Publisher>>();
+    let mut publisher = publisher.lock().unwrap();
+    publisher.collect(&mut sim)
+  };
+  emit(app, updates);
+}
+
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn set_event_rate(publisher: State<Mutex<Publisher>>, rate: f64) -> Result<(), String> {
+  if !(rate > 0.0 && rate.is_finite()) {
+    return Result::Err(String::from("Event rate must be greater than zero"));
+  }
+  let mut publisher = publisher.lock().unwrap();
+  publisher.interval = Duration::from_secs_f64(1.0 / rate);
+  return Result::Ok(());
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn select_ship(publisher: State<Mutex<Publisher>>, index: Option<usize>) {
+  let mut publisher = publisher.lock().unwrap();
+  publisher.selected = index;
+  publisher.sent_selected = None;
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::{Publisher, SimEvent, DEFAULT_EVENT_RATE};
+  use crate::{ship::ShipControls, simulation::Simulation};
+
+  #[test]
+  fn test_only_changes_are_sent() {
+    let mut sim = Simulation::new(1, 0.0, 5.0);
+    let mut publisher = Publisher::new(DEFAULT_EVENT_RATE);
+    assert!(publisher.due());
+
+    // Everything is new the first time
+    let updates = publisher.collect(&mut sim);
+    let population = updates.population.unwrap();
+    assert_eq!(population.changed.len(), sim.get_population().len());
+    assert_eq!(population.count, sim.get_population().len());
+    assert!(!publisher.due());
+
+    // Nothing has changed since
+    assert!(publisher.collect(&mut sim).population.is_none());
+
+    // Only the ship that moved is sent again, along with the line it is now towing
+    let mut states = sim.ship_states();
+    states[4].kinematics.loc.x += 1.0;
+    sim.restore_ship_states(sim.get_step(), &states).unwrap();
+    sim.tow_ship(4, 2, 1000.0).unwrap();
+    let population = publisher.collect(&mut sim).population.unwrap();
+    assert_eq!(population.changed.iter().map(|changed| changed.index).collect::<Vec<usize>>(), vec![4]);
+    assert_eq!(population.lines.map(|lines| lines.len()), Some(1));
+    assert!(population.marks.is_none());
+  }
+
+  #[test]
+  fn test_selected_ship_is_sent_when_it_changes() {
+    let mut sim = Simulation::new(1, 0.0, 5.0);
+    let mut publisher = Publisher::new(DEFAULT_EVENT_RATE);
+    publisher.selected = Some(0);
+    assert_eq!(publisher.collect(&mut sim).selected.unwrap().index, 0);
+    assert!(publisher.collect(&mut sim).selected.is_none());
+    sim.update_ship_controls(0, ShipControls::new(vec![3.0], 0.1));
+    assert_eq!(publisher.collect(&mut sim).selected.unwrap().index, 0);
+
+    // A selected ship that isn't there has nothing to send
+    publisher.selected = Some(99);
+    assert!(publisher.collect(&mut sim).selected.is_none());
+  }
+
+  #[test]
+  fn test_events_are_sent_once() {
+    let mut sim = Simulation::new(1, 0.0, 5.0);
+    let mut publisher = Publisher::new(DEFAULT_EVENT_RATE);
+    // A line pulled far past its breaking strength parts on the first step
+    sim.tow_ship(4, 2, 0.0).unwrap();
+    sim.step();
+    assert_eq!(publisher.collect(&mut sim).events, vec![SimEvent::LineParted { from: 4, to: 2 }]);
+    assert!(publisher.collect(&mut sim).events.is_empty());
+  }
+}
@@ -0,0 +1,325 @@
+use std::{
+  fmt::Write,
+  fs,
+  sync::Mutex
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::{
+  geometry::Vec2D,
+  recording::{Frame, Recorder},
+  simulation::DELTA_TIME
+};
+
+pub const EARTH_RADIUS: f64 = 6_371_000.0; // m, mean radius
+
+/// Where and when the simulation is placed in the real world, with x pointing east and y pointing north
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoOrigin {
+  /// Degrees north of the equator
+  pub latitude: f64,
+  /// Degrees east of Greenwich
+  pub longitude: f64,
+  /// Unix time in seconds at step zero
+  pub start_time: f64,
+}
+impl GeoOrigin {
+  /// Latitude and longitude of a point in simulation metres, treating the earth as flat around the origin
+  pub fn to_geo(&self, point: Vec2D) -> (f64, f64) {
+    let latitude = self.latitude + (point.y / EARTH_RADIUS).to_degrees();
+    let longitude = self.longitude + (point.x / (EARTH_RADIUS * self.latitude.to_radians().cos())).to_degrees();
+    return (latitude, longitude);
+  }
+
+  /// Simulation metres of a latitude and longitude, the inverse of `to_geo`
+  pub fn to_local(&self, latitude: f64, longitude: f64) -> Vec2D {
+    Vec2D::new(
+      (longitude - self.longitude).to_radians() * EARTH_RADIUS * self.latitude.to_radians().cos(),
+      (latitude - self.latitude).to_radians() * EARTH_RADIUS
+    )
+  }
+
+  pub fn time_at(&self, step: u64) -> f64 {
+    self.start_time + step as f64 * DELTA_TIME
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TrackFormat {
+  Gpx,
+  Kml,
+}
+
+/// The path of one ship, as the step and location of each recorded point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+  pub ship: usize,
+  pub points: Vec<(u64, Vec2D)>,
+}
+
+/// Split recorded frames into a track per ship
+pub fn tracks<'a>(frames: impl Iterator<Item = &'a Frame>, ships: Option<&[usize]>) -> Vec<Track> {
+  let mut tracks: Vec<Track> = Vec::new();
+  for frame in frames {
+    for (index, state) in frame.ships.iter().enumerate() {
+      if ships.map_or(false, |ships| !ships.contains(&index)) {
+        continue;
+      }
+      let point = (frame.step, state.kinematics.loc);
+      match tracks.iter_mut().find(|track| track.ship == index) {
+        Some(track) => track.points.push(point),
+        None => tracks.push(Track { ship: index, points: vec![point] }),
+      }
+    }
+  }
+  return tracks;
+}
+
+/// A point read from a GPS track
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackPoint {
+  pub latitude: f64,
+  pub longitude: f64,
+  /// Unix time in seconds
+  pub time: f64,
+}
+
+/// Read every timed track point from a GPX file
+pub fn parse_gpx(gpx: &str) -> Result<Vec<TrackPoint>, String> {
+  let mut points = Vec::new();
+  let mut rest = gpx;
+  while let Some(start) = rest.find("<trkpt") {
+    rest = &rest[start..];
+    let tag_end = rest.find('>').ok_or("Unterminated trkpt tag")?;
+    let tag = &rest[..tag_end];
+    let latitude = attribute(tag, "lat").ok_or("Track point without a latitude")?;
+    let longitude = attribute(tag, "lon").ok_or("Track point without a longitude")?;
+    // The point's children run until its closing tag, unless the tag closes itself
+    let body = match tag.ends_with('/') {
+      true => "",
+      false => &rest[tag_end..rest.find("</trkpt>").unwrap_or(rest.len())],
+    };
+    let time = match (body.find("<time>"), body.find("</time>")) {
+      (Some(start), Some(end)) if start < end => parse_timestamp(body[start + 6..end].trim())?,
+      _ => return Result::Err(String::from("Track point without a time")),
+    };
+    points.push(TrackPoint { latitude, longitude, time });
+    rest = &rest[tag_end..];
+  }
+  return Result::Ok(points);
+}
+
+/// The numeric value of an attribute in an XML tag
+fn attribute(tag: &str, name: &str) -> Option<f64> {
+  for quote in ['"', '\''] {
+    let pattern = format!(" {}={}", name, quote);
+    if let Some(start) = tag.find(&pattern) {
+      let value = &tag[start + pattern.len()..];
+      return value[..value.find(quote)?].trim().parse().ok();
+    }
+  }
+  return None;
+}
+
+/// Parse an ISO 8601 timestamp such as 2024-05-01T12:30:00.5Z into a Unix time
+pub fn parse_timestamp(timestamp: &str) -> Result<f64, String> {
+  let invalid = || format!("Invalid timestamp: {}", timestamp);
+  let (date, time) = timestamp.split_once('T').ok_or_else(invalid)?;
+  let date: Vec<i64> = date.split('-').map(|part| part.parse().map_err(|_| invalid())).collect::<Result<_, _>>()?;
+  if date.len() != 3 {
+    return Result::Err(invalid());
+  }
+
+  // Split off the time zone, which is Z or an offset from UTC
+  let (time, offset) = match time.find(|c| c == 'Z' || c == '+' || c == '-') {
+    Some(index) => (&time[..index], &time[index..]),
+    None => (time, "Z"),
+  };
+  let offset_seconds = match offset {
+    "Z" => 0.0,
+    _ => {
+      let sign = if offset.starts_with('-') { -1.0 } else { 1.0 };
+      let (hours, minutes) = offset[1..].split_once(':').unwrap_or((&offset[1..], "0"));
+      let hours: f64 = hours.parse().map_err(|_| invalid())?;
+      let minutes: f64 = minutes.parse().map_err(|_| invalid())?;
+      sign * (hours * 3600.0 + minutes * 60.0)
+    }
+  };
+  let time: Vec<f64> = time.split(':').map(|part| part.parse().map_err(|_| invalid())).collect::<Result<_, _>>()?;
+  if time.len() != 3 {
+    return Result::Err(invalid());
+  }
+
+  // Convert a civil date to days since 1970, see http://howardhinnant.github.io/date_algorithms.html
+  let (year, month, day) = (date[0], date[1], date[2]);
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year.rem_euclid(400);
+  let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  let days = era * 146_097 + day_of_era - 719_468;
+
+  return Result::Ok(days as f64 * 86_400.0 + time[0] * 3600.0 + time[1] * 60.0 + time[2] - offset_seconds);
+}
+
+/// Format a Unix time as an ISO 8601 UTC timestamp, as used by both GPX and KML
+pub fn format_timestamp(unix_time: f64) -> String {
+  let millis = (unix_time * 1000.0).round() as i64;
+  let (days, millis_of_day) = (millis.div_euclid(86_400_000), millis.rem_euclid(86_400_000));
+
+  // Convert days since 1970 to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+  let z = days + 719_468;
+  let era = z.div_euclid(146_097);
+  let day_of_era = z.rem_euclid(146_097);
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let shifted_month = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+  let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+  return format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+    year, month, day,
+    millis_of_day / 3_600_000, millis_of_day / 60_000 % 60, millis_of_day / 1000 % 60, millis_of_day % 1000
+  );
+}
+
+pub fn write_gpx(tracks: &[Track], origin: &GeoOrigin) -> String {
+  let mut gpx = String::new();
+  gpx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  gpx.push_str("<gpx version=\"1.1\" creator=\"Sail\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n");
+  for track in tracks {
+    writeln!(gpx, "  <trk>\n    <name>Ship {}</name>\n    <trkseg>", track.ship).unwrap();
+    for (step, loc) in &track.points {
+      let (latitude, longitude) = origin.to_geo(*loc);
+      writeln!(
+        gpx, "      <trkpt lat=\"{:.8}\" lon=\"{:.8}\"><time>{}</time></trkpt>",
+        latitude, longitude, format_timestamp(origin.time_at(*step))
+      ).unwrap();
+    }
+    gpx.push_str("    </trkseg>\n  </trk>\n");
+  }
+  gpx.push_str("</gpx>\n");
+  return gpx;
+}
+
+/// Write the tracks as KML, using the gx:Track extension so that each point keeps its time
+pub fn write_kml(tracks: &[Track], origin: &GeoOrigin) -> String {
+  let mut kml = String::new();
+  kml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  kml.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\" xmlns:gx=\"http://www.google.com/kml/ext/2.2\">\n<Document>\n");
+  for track in tracks {
+    writeln!(kml, "  <Placemark>\n    <name>Ship {}</name>\n    <gx:Track>", track.ship).unwrap();
+    for (step, _) in &track.points {
+      writeln!(kml, "      <when>{}</when>", format_timestamp(origin.time_at(*step))).unwrap();
+    }
+    for (_, loc) in &track.points {
+      // KML puts longitude first
+      let (latitude, longitude) = origin.to_geo(*loc);
+      writeln!(kml, "      <gx:coord>{:.8} {:.8} 0</gx:coord>", longitude, latitude).unwrap();
+    }
+    kml.push_str("    </gx:Track>\n  </Placemark>\n");
+  }
+  kml.push_str("</Document>\n</kml>\n");
+  return kml;
+}
+
+
+/// Write the recorded path of each ship to a GPX or KML file
+#[tauri::command(rename_all = "snake_case")]
+pub fn export_tracks(
+  recorder: State<Mutex<Recorder>>,
+  path: String,
+  format: TrackFormat,
+  origin: GeoOrigin,
+  ships: Option<Vec<usize>>
+) -> Result<(), String> {
+  let recorder = recorder.lock().unwrap();
+  let tracks = tracks(recorder.get_frames().iter(), ships.as_deref());
+  let contents = match format {
+    TrackFormat::Gpx => write_gpx(&tracks, &origin),
+    TrackFormat::Kml => write_kml(&tracks, &origin),
+  };
+  return fs::write(path, contents).map_err(|e| e.to_string());
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::{format_timestamp, parse_gpx, parse_timestamp, write_gpx, GeoOrigin, Track, TrackPoint, EARTH_RADIUS};
+  use crate::geometry::Vec2D;
+
+  #[test]
+  fn test_parse_timestamp() {
+    assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Ok(0.0));
+    assert_eq!(parse_timestamp("2024-05-01T12:30:00.5Z"), Ok(1_714_566_600.5));
+    // Offsets are from UTC, and a missing zone is taken as UTC
+    assert_eq!(parse_timestamp("2024-05-01T14:30:00.5+02:00"), Ok(1_714_566_600.5));
+    assert_eq!(parse_timestamp("2024-05-01T07:00:00.5-05:30"), Ok(1_714_566_600.5));
+    assert_eq!(parse_timestamp("2024-05-01T12:30:00.5"), Ok(1_714_566_600.5));
+    assert_eq!(parse_timestamp("2000-03-01T00:00:00Z"), Ok(951_868_800.0));
+    assert!(parse_timestamp("2024-05-01 12:30:00Z").is_err());
+    assert!(parse_timestamp("2024-05T12:30:00Z").is_err());
+    assert!(parse_timestamp("2024-05-01T12:30Z").is_err());
+  }
+
+  #[test]
+  fn test_parse_gpx() {
+    let gpx = r#"<?xml version="1.0"?>
+      <gpx><trk><trkseg>
+        <trkpt lat="50.5" lon="-1.25"><ele>0</ele><time>1970-01-01T00:00:01Z</time></trkpt>
+        <trkpt lon='-1.5' lat='50.75'>
+          <time> 1970-01-01T00:00:02Z </time>
+        </trkpt>
+      </trkseg></trk></gpx>"#;
+    assert_eq!(parse_gpx(gpx), Ok(vec![
+      TrackPoint { latitude: 50.5, longitude: -1.25, time: 1.0 },
+      TrackPoint { latitude: 50.75, longitude: -1.5, time: 2.0 },
+    ]));
+    assert_eq!(parse_gpx("<gpx></gpx>"), Ok(Vec::new()));
+    assert!(parse_gpx(r#"<trkpt lat="50.5" lon="-1.25"/>"#).is_err());
+    assert!(parse_gpx(r#"<trkpt lat="50.5"><time>1970-01-01T00:00:01Z</time></trkpt>"#).is_err());
+    assert!(parse_gpx(r#"<trkpt lat="50.5" lon="-1.25"><time>yesterday</time></trkpt>"#).is_err());
+  }
+
+  #[test]
+  fn test_format_timestamp() {
+    assert_eq!(format_timestamp(0.0), "1970-01-01T00:00:00.000Z");
+    assert_eq!(format_timestamp(1_714_566_600.5), "2024-05-01T12:30:00.500Z");
+    assert_eq!(format_timestamp(951_868_799.999_6), "2000-03-01T00:00:00.000Z");
+    assert_eq!(format_timestamp(-1.0), "1969-12-31T23:59:59.000Z");
+    for time in [0.0, 951_782_400.25, 1_714_566_600.5, 4_102_444_800.0] {
+      assert_eq!(parse_timestamp(&format_timestamp(time)), Ok(time));
+    }
+  }
+
+  #[test]
+  fn test_geo_round_trip() {
+    let origin = GeoOrigin { latitude: 50.8, longitude: -1.3, start_time: 0.0 };
+    for point in [Vec2D::zeros(), Vec2D::new(1234.5, -678.9), Vec2D::new(-5000.0, 5000.0)] {
+      let (latitude, longitude) = origin.to_geo(point);
+      assert!(origin.to_local(latitude, longitude).dist(point) < 1e-6);
+    }
+    // North is up and east is right, with a degree of latitude the same length everywhere
+    let (latitude, longitude) = origin.to_geo(Vec2D::new(0.0, EARTH_RADIUS.to_radians()));
+    assert!((latitude - 51.8).abs() < 1e-9 && longitude == origin.longitude);
+    let (latitude, longitude) = origin.to_geo(Vec2D::new(100.0, 0.0));
+    assert!(latitude == origin.latitude && longitude > origin.longitude);
+  }
+
+  #[test]
+  fn test_gpx_round_trip() {
+    // Exported tracks read back in at the same places, at the origin's start time plus the step
+    let origin = GeoOrigin { latitude: 50.8, longitude: -1.3, start_time: 1_714_566_600.0 };
+    let track = Track { ship: 0, points: vec![(0, Vec2D::zeros()), (30, Vec2D::new(10.0, -20.0))] };
+    let points = parse_gpx(&write_gpx(std::slice::from_ref(&track), &origin)).unwrap();
+    assert_eq!(points.len(), 2);
+    for (point, (step, loc)) in points.iter().zip(&track.points) {
+      assert!((point.time - origin.time_at(*step)).abs() < 1e-3);
+      assert!(origin.to_local(point.latitude, point.longitude).dist(*loc) < 0.01);
+    }
+  }
+}
@@ -56,6 +56,16 @@ impl Vec2D {
     f64::sqrt(self.x*self.x + self.y*self.y)
   }
 
+  /// The dot product of the two vectors
+  pub fn dot(self, other: Self) -> f64 {
+    self.x*other.x + self.y*other.y
+  }
+
+  /// The z component of the cross product of the two vectors, positive when other is anticlockwise of this
+  pub fn cross(self, other: Self) -> f64 {
+    self.x*other.y - self.y*other.x
+  }
+
   /// Convert to a unit vector
   pub fn unit(self) -> Self {
     let hypo = self.magnitude();
@@ -117,6 +127,59 @@ pub fn bound(value: f64, min: f64, max: f64) -> f64 {
   ((value - min).rem_euclid(max - min)) + min
 }
 
+/// A rectangle with its length along the heading and its width across it, such as the outline of a hull
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rectangle {
+  pub center: Vec2D,
+  pub heading: f64,
+  pub length: f64,
+  pub width: f64,
+}
+impl Rectangle {
+  pub fn new(center: Vec2D, heading: f64, length: f64, width: f64) -> Self {
+    Self { center, heading, length, width }
+  }
+
+  /// Whether the point lands within the rectangle
+  pub fn contains(&self, point: Vec2D) -> bool {
+    let local = (point - self.center).rotate(-self.heading);
+    local.x.abs() <= self.length * 0.5 && local.y.abs() <= self.width * 0.5
+  }
+
+  /// Distance from the center to the corners
+  pub fn radius(&self) -> f64 {
+    Vec2D::new(self.length, self.width).magnitude() * 0.5
+  }
+
+  /// Unit vectors along the length and across the width
+  fn axes(&self) -> [Vec2D; 2] {
+    let along = Vec2D::from_angle(self.heading);
+    [along, Vec2D::new(-along.y, along.x)]
+  }
+
+  pub fn corners(&self) -> [Vec2D; 4] {
+    let [along, across] = self.axes();
+    let (x, y) = (along.scale(self.length * 0.5), across.scale(self.width * 0.5));
+    [self.center + x + y, self.center - x + y, self.center - x - y, self.center + x - y]
+  }
+
+  /// Whether the rectangles touch, found by looking for a side of either one that separates them
+  pub fn overlaps(&self, other: &Rectangle) -> bool {
+    let (ours, theirs) = (self.corners(), other.corners());
+    let [our_along, our_across] = self.axes();
+    let [their_along, their_across] = other.axes();
+    return [our_along, our_across, their_along, their_across].iter().all(|axis| {
+      let axis = *axis;
+      let project = |corners: &[Vec2D; 4]| corners.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), corner| {
+        let distance = corner.dot(axis);
+        (f64::min(min, distance), f64::max(max, distance))
+      });
+      let ((our_min, our_max), (their_min, their_max)) = (project(&ours), project(&theirs));
+      our_min <= their_max && their_min <= our_max
+    });
+  }
+}
+
 /// Rotate an angle by 180 degrees, keeping it within the range of [-PI, PI)
 pub fn invert_angle(angle: f64) -> f64 {
   bound_angle(angle + PI)
@@ -133,7 +196,7 @@ pub fn find_angle(a: f64, b: f64, c: f64) -> f64 {
 #[cfg(test)]
 mod tests {
   use std::f64::consts::PI;
-  use super::{bound_angle, Vec2D};
+  use super::{bound_angle, Rectangle, Vec2D};
 
   const F64_DIFF_MARGIN: f64 = 1.0e-15;
   const RADIANS_45: f64 = PI / 4.0;
@@ -260,6 +323,18 @@ mod tests {
     let diff = a - b;
     assert!(diff.abs() < F64_DIFF_MARGIN);
   }
+
+  #[test]
+  fn test_rectangles_overlap() {
+    let hull = Rectangle::new(Vec2D::zeros(), 0.0, 4.0, 1.0);
+    // Side by side, just apart and just touching
+    assert!(!hull.overlaps(&Rectangle::new(Vec2D::new(0.0, 1.1), 0.0, 4.0, 1.0)));
+    assert!(hull.overlaps(&Rectangle::new(Vec2D::new(0.0, 0.9), 0.0, 4.0, 1.0)));
+    // Crossing with no corner inside the other
+    assert!(hull.overlaps(&Rectangle::new(Vec2D::zeros(), RADIANS_45 * 2.0, 4.0, 1.0)));
+    // Staggered, where the circles round the hulls overlap but the hulls don't
+    assert!(!hull.overlaps(&Rectangle::new(Vec2D::new(3.0, 1.2), 0.0, 4.0, 1.0)));
+  }
 }
 
 
@@ -0,0 +1,261 @@
+use std::{fs, sync::Mutex};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::{
+  drawing::ShipShape,
+  geo::{parse_gpx, GeoOrigin, TrackPoint},
+  geometry::{bound_angle, Rectangle, Vec2D},
+  mooring::Tether,
+  physics::{Force, ForceSink, Kinematics},
+  propulsion::Engine,
+  ship::{Ship, ShipControls, ShipState},
+  simulation::{Simulation, DELTA_TIME}
+};
+
+pub const GHOST_LENGTH: f64 = 6.0; // m, only for drawing and picking
+pub const GHOST_WIDTH: f64 = 2.0; // m
+const MIN_HEADING_SPEED: f64 = 0.1; // m/s, below which GPS noise swamps the direction of travel
+
+/// A boat that follows a recorded track instead of being moved by forces, to compare against simulated ships
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GhostShip {
+  /// Seconds since the origin's start time and the location at that time
+  #[serde(skip)]
+  pub track: Vec<(f64, Vec2D)>,
+  /// Seconds since the origin's start time, which is when step zero was
+  pub time: f64,
+
+  pub loc: Vec2D,
+  pub vel: Vec2D,
+  pub rot_vel: f64,
+  pub heading: f64,
+  pub sail_angles: Vec<f64>,
+  pub controls: ShipControls,
+  pub tethers: Vec<Tether>,
+}
+impl GhostShip {
+  /// Follow a GPS track, placed in the simulation relative to the given origin in both space and time, and moved to
+  /// where it is at the given step
+  pub fn from_track(points: &[TrackPoint], origin: &GeoOrigin, step: u64) -> Result<Self, String> {
+    if points.is_empty() {
+      return Result::Err(String::from("The track has no points"));
+    }
+    let mut track: Vec<(f64, Vec2D)> = points.iter()
+      .map(|point| (point.time - origin.start_time, origin.to_local(point.latitude, point.longitude)))
+      .collect();
+    track.sort_by(|a, b| a.0.total_cmp(&b.0));
+    // Points logged at the same time can't give a speed
+    track.dedup_by(|later, earlier| later.0 == earlier.0);
+
+    let mut ghost = Self {
+      track,
+      time: 0.0,
+      loc: Vec2D::zeros(),
+      vel: Vec2D::zeros(),
+      rot_vel: 0.0,
+      heading: 0.0,
+      sail_angles: Vec::new(),
+      controls: ShipControls::new(Vec::new(), 0.0),
+      tethers: Vec::new(),
+    };
+    ghost.follow_track(step as f64 * DELTA_TIME);
+    return Result::Ok(ghost);
+  }
+
+  /// Move to where the track was at the given time, taking speed and heading from the leg being sailed
+  fn follow_track(&mut self, time: f64) {
+    self.time = time;
+    let next = self.track.partition_point(|(point_time, _)| *point_time <= time);
+    if next == 0 || next == self.track.len() {
+      // Waiting at the start or finished at the end
+      self.loc = if next == 0 { self.track[0].1 } else { self.track[next - 1].1 };
+      self.vel = Vec2D::zeros();
+      self.rot_vel = 0.0;
+      return;
+    }
+    let (start_time, start) = self.track[next - 1];
+    let (end_time, end) = self.track[next];
+    let leg = end - start;
+    let fraction = (time - start_time) / (end_time - start_time);
+    self.loc = start + leg.scale(fraction);
+    self.vel = leg.scale(1.0 / (end_time - start_time));
+
+    let previous_heading = self.heading;
+    if self.vel.magnitude() > MIN_HEADING_SPEED {
+      self.heading = self.vel.to_angle();
+    }
+    self.rot_vel = bound_angle(self.heading - previous_heading) / DELTA_TIME;
+  }
+}
+impl Ship for GhostShip {
+  fn update(&mut self, _wind_angle: f64, _wind_speed: f64, _current: Vec2D, _external_forces: &[Force]) {
+    self.follow_track(self.time + DELTA_TIME);
+  }
+
+  fn add_forces(&self, _wind_angle: f64, _wind_speed: f64, _current: Vec2D, _sink: &mut dyn ForceSink) {
+    // The track is followed without any forces
+  }
+
+  fn kinematics(&self) -> Kinematics {
+    Kinematics::new(self.loc, self.vel, self.rot_vel, self.heading)
+  }
+
+  fn controls(&self) -> &ShipControls {
+    &self.controls
+  }
+
+  fn set_controls(&mut self, _controls: ShipControls) {
+    // The track can't be steered
+  }
+
+  fn tethers(&self) -> &[Tether] {
+    &self.tethers
+  }
+
+  fn tethers_mut(&mut self) -> &mut Vec<Tether> {
+    &mut self.tethers
+  }
+
+  fn engine(&self) -> Option<&Engine> {
+    None
+  }
+
+  fn bow_offset(&self) -> Vec2D {
+    Vec2D::at_x(GHOST_LENGTH * 0.5)
+  }
+
+  fn stern_offset(&self) -> Vec2D {
+    Vec2D::at_x(-GHOST_LENGTH * 0.5)
+  }
+
+  fn hull(&self) -> Rectangle {
+    Rectangle::new(self.loc, self.heading, GHOST_LENGTH, GHOST_WIDTH)
+  }
+
+  fn shape(&self) -> ShipShape {
+    ShipShape::from_ghost(self)
+  }
+
+  fn to_json(&self) -> serde_json::Value {
+    serde_json::to_value(self).unwrap()
+  }
+
+  fn state(&self) -> ShipState {
+    ShipState {
+      kinematics: self.kinematics(),
+      sail_angles: Vec::new(),
+      controls: self.controls.clone(),
+      tethers: Vec::new(),
+      fuel: None,
+      track_time: Some(self.time),
+    }
+  }
+
+  fn restore(&mut self, state: &ShipState) {
+    self.follow_track(state.track_time.unwrap_or(self.time));
+    self.heading = state.kinematics.heading;
+  }
+
+  fn clone_box(&self) -> Box<dyn Ship> {
+    Box::new(self.clone())
+  }
+
+  fn solid(&self) -> bool {
+    false
+  }
+}
+
+
+/// Add a ghost boat following a GPX track, placed relative to the origin, or starting at 0,0 from the current step if
+/// there isn't one
+#[tauri::command(rename_all = "snake_case")]
+pub fn import_gpx_track(sim: State<Mutex<Simulation>>, path: String, origin: Option<GeoOrigin>) -> Result<usize, String> {
+  let gpx = fs::read_to_string(path).map_err(|e| e.to_string())?;
+  let points = parse_gpx(&gpx)?;
+  let mut sim = sim.lock().unwrap();
+  let step = sim.get_step();
+  let origin = match (origin, points.first()) {
+    (Some(origin), _) => origin,
+    (None, Some(first)) => GeoOrigin {
+      latitude: first.latitude,
+      longitude: first.longitude,
+      start_time: first.time - step as f64 * DELTA_TIME,
+    },
+    (None, None) => return Result::Err(String::from("The track has no points")),
+  };
+  let ghost = GhostShip::from_track(&points, &origin, step)?;
+  return Result::Ok(sim.add_ship(Box::new(ghost)));
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::GhostShip;
+  use crate::{
+    events::SimEvent,
+    geo::{GeoOrigin, TrackPoint},
+    geometry::Vec2D,
+    ship::Ship,
+    simulation::{Simulation, DELTA_TIME}
+  };
+
+  const ORIGIN: GeoOrigin = GeoOrigin { latitude: 50.0, longitude: -1.0, start_time: 1000.0 };
+
+  /// A track heading north at 1 m/s from the origin, starting the given number of seconds after step zero
+  fn track(delay: f64) -> Vec<TrackPoint> {
+    return (0..=10).map(|second| TrackPoint {
+      latitude: ORIGIN.to_geo(Vec2D::new(0.0, second as f64)).0,
+      longitude: ORIGIN.longitude,
+      time: ORIGIN.start_time + delay + second as f64,
+    }).collect();
+  }
+
+  #[test]
+  fn test_follows_the_origin_start_time() {
+    // Waits at the start of a track that hasn't started yet
+    let mut ghost = GhostShip::from_track(&track(2.0), &ORIGIN, 0).unwrap();
+    for _ in 0..60 {
+      ghost.update(0.0, 0.0, Vec2D::zeros(), &[]);
+    }
+    assert!(ghost.loc.dist(Vec2D::zeros()) < 1e-6);
+
+    // Once it has started, it sails along the track at the time of the step
+    for _ in 0..30 {
+      ghost.update(0.0, 0.0, Vec2D::zeros(), &[]);
+    }
+    assert!((ghost.loc.y - 1.0).abs() < 1e-3, "{}", ghost.loc.y);
+    let imported_later = GhostShip::from_track(&track(2.0), &ORIGIN, 90).unwrap();
+    assert!(imported_later.loc.dist(ghost.loc) < 1e-6);
+  }
+
+  #[test]
+  fn test_restores_its_time_on_the_track() {
+    // A track that doubles back on itself, so the location alone doesn't say where along it the ghost was
+    let mut points = track(0.0);
+    let back = track(20.0).into_iter().zip(track(0.0).into_iter().rev());
+    points.extend(back.map(|(later, there)| TrackPoint { time: later.time, ..there }));
+    let mut ghost = GhostShip::from_track(&points, &ORIGIN, (25.0 / DELTA_TIME) as u64).unwrap();
+    let state = ghost.state();
+    let vel = ghost.vel;
+    assert!(vel.y < 0.0);
+
+    ghost.restore(&GhostShip::from_track(&points, &ORIGIN, 0).unwrap().state());
+    ghost.restore(&state);
+    assert!((ghost.time - 25.0).abs() < 1e-9);
+    assert_eq!(ghost.vel, vel);
+  }
+
+  #[test]
+  fn test_ghosts_do_not_collide() {
+    let mut sim = Simulation::new(1, 0.0, 5.0);
+    let loc = sim.get_ship(0).unwrap().kinematics().loc;
+    let origin = GeoOrigin { latitude: ORIGIN.latitude, longitude: ORIGIN.longitude, start_time: 0.0 };
+    let (latitude, longitude) = origin.to_geo(loc);
+    let points = [TrackPoint { latitude, longitude, time: 0.0 }, TrackPoint { latitude, longitude, time: 60.0 }];
+    sim.add_ship(Box::new(GhostShip::from_track(&points, &origin, 0).unwrap()));
+    sim.run(30, None, |_| {});
+    assert!(!sim.drain_events().iter().any(|event| matches!(event, SimEvent::Collision { .. })));
+  }
+}
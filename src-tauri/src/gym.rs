@@ -0,0 +1,411 @@
+use std::{
+  io::{self, BufRead, BufReader, Write},
+  net::{TcpListener, TcpStream},
+  thread
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  geometry::{bound_angle, Vec2D},
+  instruments::InstrumentsConfig,
+  physics::{calculate_apparent_wind_simple, Kinematics},
+  simulation::Simulation
+};
+
+pub const DEFAULT_PORT: u16 = 7410;
+
+/// Values that can make up an observation, each giving one or more numbers
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ObservationField {
+  /// x and y in metres
+  Position,
+  /// x and y in metres per second
+  Velocity,
+  Speed,
+  RotVelocity,
+  /// Radians, anticlockwise from the x axis
+  Heading,
+  /// Angle relative to the bow and speed of the wind felt on the ship
+  ApparentWind,
+  SailAngle(usize),
+  Rudder,
+  Mainsheet(usize),
+  Throttle,
+  /// Bearing relative to the bow and distance to the mark, or zeros if there is no mark
+  Mark,
+  /// Every reading of the ship's instruments, to train against what a sailor would see rather than the exact state
+  Instruments,
+}
+impl ObservationField {
+  fn names(&self) -> Vec<String> {
+    match self {
+      ObservationField::Position => vec![String::from("x"), String::from("y")],
+      ObservationField::Velocity => vec![String::from("vel_x"), String::from("vel_y")],
+      ObservationField::Speed => vec![String::from("speed")],
+      ObservationField::RotVelocity => vec![String::from("rot_vel")],
+      ObservationField::Heading => vec![String::from("heading")],
+      ObservationField::ApparentWind => vec![String::from("apparent_wind_angle"), String::from("apparent_wind_speed")],
+      ObservationField::SailAngle(sail) => vec![format!("sail_{}_angle", sail)],
+      ObservationField::Rudder => vec![String::from("rudder_angle")],
+      ObservationField::Mainsheet(sail) => vec![format!("sail_{}_mainsheet", sail)],
+      ObservationField::Throttle => vec![String::from("throttle")],
+      ObservationField::Mark => vec![String::from("mark_bearing"), String::from("mark_distance")],
+      ObservationField::Instruments => [
+        "speed_through_water", "speed_over_ground", "compass_heading", "apparent_wind_angle",
+        "apparent_wind_speed", "true_wind_angle", "true_wind_speed", "heel",
+      ].iter().map(|name| format!("instrument_{}", name)).collect(),
+    }
+  }
+}
+
+/// Controls that an action sets, each taking one number
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ActionField {
+  Rudder,
+  Mainsheet(usize),
+  Reef(usize),
+  Traveller(usize),
+  Vang(usize),
+  Throttle,
+  CrewFore,
+  CrewAthwart,
+}
+impl ActionField {
+  fn name(&self) -> String {
+    match self {
+      ActionField::Rudder => String::from("rudder_angle"),
+      ActionField::Mainsheet(sail) => format!("sail_{}_mainsheet", sail),
+      ActionField::Reef(sail) => format!("sail_{}_reef", sail),
+      ActionField::Traveller(sail) => format!("sail_{}_traveller", sail),
+      ActionField::Vang(sail) => format!("sail_{}_vang", sail),
+      ActionField::Throttle => String::from("throttle"),
+      ActionField::CrewFore => String::from("crew_x"),
+      ActionField::CrewAthwart => String::from("crew_y"),
+    }
+  }
+}
+
+/// Scores how well the ship did over one action
+pub trait Reward: Send {
+  fn reward(&mut self, before: Kinematics, after: Kinematics, sim: &Simulation) -> f64;
+
+  /// Whether the episode is over, such as when the mark has been reached
+  fn done(&self, _after: Kinematics, _sim: &Simulation) -> bool {
+    false
+  }
+}
+
+/// Metres made good directly upwind
+pub struct VmgReward;
+impl Reward for VmgReward {
+  fn reward(&mut self, before: Kinematics, after: Kinematics, sim: &Simulation) -> f64 {
+    let upwind = Vec2D::from_angle(sim.get_settings().wind_angle);
+    return (after.loc - before.loc).dot(upwind);
+  }
+}
+
+/// Metres closer to the mark, finishing once the ship is within the radius
+pub struct MarkProgressReward {
+  pub mark: Vec2D,
+  pub radius: f64,
+}
+impl Reward for MarkProgressReward {
+  fn reward(&mut self, before: Kinematics, after: Kinematics, _sim: &Simulation) -> f64 {
+    before.loc.dist(self.mark) - after.loc.dist(self.mark)
+  }
+
+  fn done(&self, after: Kinematics, _sim: &Simulation) -> bool {
+    after.loc.dist(self.mark) <= self.radius
+  }
+}
+
+/// The built in rewards, for choosing one without writing code
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RewardConfig {
+  Vmg,
+  MarkProgress { mark: Vec2D, radius: f64 },
+}
+impl RewardConfig {
+  pub fn build(&self) -> Box<dyn Reward> {
+    match *self {
+      RewardConfig::Vmg => Box::new(VmgReward),
+      RewardConfig::MarkProgress { mark, radius } => Box::new(MarkProgressReward { mark, radius }),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnvConfig {
+  /// Index of the ship being steered in the population
+  pub ship: usize,
+  pub observation: Vec<ObservationField>,
+  pub action: Vec<ActionField>,
+  pub wind_angle: f64,
+  pub wind_speed: f64,
+  /// Simulation steps taken for each action
+  pub steps_per_action: u64,
+  /// Simulation steps before the episode ends
+  pub max_steps: u64,
+  pub reward: RewardConfig,
+  /// Where the Mark observation points to
+  pub mark: Option<Vec2D>,
+  /// The instruments read by the Instruments observation, or typical ones if not given
+  #[serde(default)]
+  pub instruments: Option<InstrumentsConfig>,
+}
+impl Default for EnvConfig {
+  fn default() -> Self {
+    Self {
+      ship: 0,
+      observation: vec![
+        ObservationField::Velocity, ObservationField::Heading, ObservationField::ApparentWind,
+        ObservationField::Rudder, ObservationField::Mainsheet(0),
+      ],
+      action: vec![ActionField::Rudder, ActionField::Mainsheet(0)],
+      wind_angle: 0.0,
+      wind_speed: 5.0,
+      steps_per_action: 3,
+      max_steps: 3000,
+      reward: RewardConfig::Vmg,
+      mark: None,
+      instruments: None,
+    }
+  }
+}
+
+/// Names of each number in an observation and an action, in order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Spaces {
+  pub observation: Vec<String>,
+  pub action: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepResult {
+  pub observation: Vec<f64>,
+  pub reward: f64,
+  pub done: bool,
+}
+
+/// A simulation wrapped for training agents, which steer one ship through actions and see it through observations
+pub struct SailingEnv {
+  config: EnvConfig,
+  reward: Box<dyn Reward>,
+  sim: Simulation,
+}
+impl SailingEnv {
+  /// Create an environment using the reward chosen in the config
+  pub fn new(config: EnvConfig) -> Result<Self, String> {
+    let reward = config.reward.build();
+    return Self::with_reward(config, reward);
+  }
+
+  /// Create an environment with any reward, such as one written outside this crate
+  pub fn with_reward(config: EnvConfig, reward: Box<dyn Reward>) -> Result<Self, String> {
+    let sim = Simulation::new(0, config.wind_angle, config.wind_speed);
+    if sim.get_ship(config.ship).is_none() {
+      return Result::Err(String::from("No such ship"));
+    }
+    let mut env = Self { config, reward, sim };
+    env.fit_instruments()?;
+    return Result::Ok(env);
+  }
+
+  fn fit_instruments(&mut self) -> Result<(), String> {
+    if self.config.observation.contains(&ObservationField::Instruments) {
+      let config = self.config.instruments.unwrap_or_default();
+      self.sim.set_instruments(self.config.ship, Some(config))?;
+    }
+    return Result::Ok(());
+  }
+
+  pub fn get_simulation(&self) -> &Simulation {
+    &self.sim
+  }
+
+  pub fn spaces(&self) -> Spaces {
+    Spaces {
+      observation: self.config.observation.iter().flat_map(|field| field.names()).collect(),
+      action: self.config.action.iter().map(|field| field.name()).collect(),
+    }
+  }
+
+  /// Start a new episode
+  pub fn reset(&mut self, seed: u64) -> Vec<f64> {
+    self.sim = Simulation::new(seed, self.config.wind_angle, self.config.wind_speed);
+    // The instruments were checked when the environment was made
+    self.fit_instruments().unwrap();
+    return self.observation();
+  }
+
+  /// Set the controls from the action, then simulate until the next action is due
+  pub fn step(&mut self, action: &[f64]) -> Result<StepResult, String> {
+    if action.len() != self.config.action.len() {
+      return Result::Err(format!("Expected {} action values, got {}", self.config.action.len(), action.len()));
+    }
+    let ship = self.sim.get_ship(self.config.ship).unwrap();
+    let mut controls = ship.controls().clone();
+    for (field, value) in self.config.action.iter().zip(action.iter()) {
+      let value = *value;
+      match *field {
+        ActionField::Rudder => controls.rudder_angle = value,
+        ActionField::Mainsheet(sail) => set_sail_control(&mut controls.mainsheet_lengths, sail, value)?,
+        ActionField::Reef(sail) => set_sail_control(&mut controls.reef_amounts, sail, value)?,
+        ActionField::Traveller(sail) => set_sail_control(&mut controls.traveller_positions, sail, value)?,
+        ActionField::Vang(sail) => set_sail_control(&mut controls.vang_tensions, sail, value)?,
+        ActionField::Throttle => controls.throttle = value,
+        ActionField::CrewFore => controls.crew_position.x = value,
+        ActionField::CrewAthwart => controls.crew_position.y = value,
+      }
+    }
+    self.sim.update_ship_controls(self.config.ship, controls);
+
+    let before = self.kinematics();
+    for _ in 0..self.config.steps_per_action {
+      self.sim.step();
+    }
+    let after = self.kinematics();
+
+    let reward = self.reward.reward(before, after, &self.sim);
+    let done = self.sim.get_step() >= self.config.max_steps || self.reward.done(after, &self.sim);
+    return Result::Ok(StepResult { observation: self.observation(), reward, done });
+  }
+
+  fn kinematics(&self) -> Kinematics {
+    self.sim.get_ship(self.config.ship).unwrap().kinematics()
+  }
+
+  pub fn observation(&self) -> Vec<f64> {
+    let ship = self.sim.get_ship(self.config.ship).unwrap();
+    let state = ship.state();
+    let kinematics = state.kinematics;
+    let settings = self.sim.get_settings();
+    let mut observation = Vec::new();
+    for field in &self.config.observation {
+      match *field {
+        ObservationField::Position => observation.extend([kinematics.loc.x, kinematics.loc.y]),
+        ObservationField::Velocity => observation.extend([kinematics.vel.x, kinematics.vel.y]),
+        ObservationField::Speed => observation.push(kinematics.vel.magnitude()),
+        ObservationField::RotVelocity => observation.push(kinematics.rot_vel),
+        ObservationField::Heading => observation.push(kinematics.heading),
+        ObservationField::ApparentWind => {
+          let apparent_wind = calculate_apparent_wind_simple(kinematics.vel, settings.wind_angle, settings.wind_speed);
+          // The direction the wind comes from, relative to the bow
+          let angle = bound_angle(apparent_wind.to_angle() + std::f64::consts::PI - kinematics.heading);
+          observation.extend([angle, apparent_wind.magnitude()]);
+        }
+        ObservationField::SailAngle(sail) => observation.push(state.sail_angles.get(sail).copied().unwrap_or(0.0)),
+        ObservationField::Rudder => observation.push(state.controls.rudder_angle),
+        ObservationField::Mainsheet(sail) => observation.push(state.controls.mainsheet_lengths.get(sail).copied().unwrap_or(0.0)),
+        ObservationField::Throttle => observation.push(state.controls.throttle),
+        ObservationField::Mark => match self.config.mark {
+          Some(mark) => {
+            let to_mark = mark - kinematics.loc;
+            observation.extend([bound_angle(to_mark.to_angle() - kinematics.heading), to_mark.magnitude()]);
+          }
+          None => observation.extend([0.0, 0.0]),
+        },
+        ObservationField::Instruments => {
+          let readings = self.sim.readings(self.config.ship).unwrap();
+          observation.extend([
+            readings.speed_through_water, readings.speed_over_ground, readings.compass_heading, readings.apparent_wind_angle,
+            readings.apparent_wind_speed, readings.true_wind_angle, readings.true_wind_speed, readings.heel,
+          ]);
+        }
+      }
+    }
+    return observation;
+  }
+}
+
+fn set_sail_control(values: &mut Vec<f64>, sail: usize, value: f64) -> Result<(), String> {
+  match values.get_mut(sail) {
+    Some(control) => {
+      *control = value;
+      return Result::Ok(());
+    }
+    None => return Result::Err(format!("The ship has no sail {}", sail)),
+  }
+}
+
+/// A request to the environment server, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum Request {
+  /// Replace the environment with one using this config
+  Configure(EnvConfig),
+  Reset { seed: u64 },
+  Step { action: Vec<f64> },
+  Spaces,
+}
+
+/// Serve environments to clients of the listener, one per connection, answering each request line with a JSON line
+/// of `{"Ok": ...}` or `{"Err": "..."}`
+pub fn serve(listener: TcpListener) -> io::Result<()> {
+  for stream in listener.incoming() {
+    let stream = stream?;
+    // Mistakes in requests are answered, so an error here only means the client has gone
+    thread::spawn(move || handle_client(stream));
+  }
+  return Ok(());
+}
+
+fn handle_client(stream: TcpStream) -> io::Result<()> {
+  let mut writer = stream.try_clone()?;
+  let mut env = SailingEnv::new(EnvConfig::default()).unwrap();
+  for line in BufReader::new(stream).lines() {
+    let line = line?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    let response: Result<serde_json::Value, String> = match serde_json::from_str::<Request>(&line) {
+      Ok(Request::Configure(config)) => SailingEnv::new(config).map(|new_env| {
+        env = new_env;
+        serde_json::to_value(env.spaces()).unwrap()
+      }),
+      Ok(Request::Reset { seed }) => Ok(serde_json::to_value(env.reset(seed)).unwrap()),
+      Ok(Request::Step { action }) => env.step(&action).map(|result| serde_json::to_value(result).unwrap()),
+      Ok(Request::Spaces) => Ok(serde_json::to_value(env.spaces()).unwrap()),
+      Err(error) => Err(error.to_string()),
+    };
+    writeln!(writer, "{}", serde_json::to_string(&response).unwrap())?;
+  }
+  return Ok(());
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    thread
+  };
+  use super::serve;
+
+  #[test]
+  fn test_reset_and_step_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || serve(listener));
+
+    let mut client = TcpStream::connect(address).unwrap();
+    let mut lines = BufReader::new(client.try_clone().unwrap()).lines();
+    let mut request = |json: &str| -> serde_json::Value {
+      writeln!(client, "{}", json).unwrap();
+      return serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+    };
+
+    let spaces = request(r#""Spaces""#);
+    let observation_size = spaces["Ok"]["observation"].as_array().unwrap().len();
+    let reset = request(r#"{"Reset": {"seed": 7}}"#);
+    assert_eq!(reset["Ok"].as_array().unwrap().len(), observation_size);
+    let step = request(r#"{"Step": {"action": [0.1, 4.0]}}"#);
+    assert_eq!(step["Ok"]["observation"].as_array().unwrap().len(), observation_size);
+    assert!(step["Ok"]["reward"].is_f64());
+    assert_eq!(step["Ok"]["done"], false);
+    // An action of the wrong size is refused without ending the connection
+    assert!(request(r#"{"Step": {"action": [0.1]}}"#)["Err"].is_string());
+    assert!(request(r#""Spaces""#)["Ok"].is_object());
+  }
+}
@@ -0,0 +1,363 @@
+use std::{f64::consts::PI, sync::Mutex};
+
+use rand::{rngs::StdRng, Rng};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+use tauri::State;
+
+use crate::{
+  geometry::bound_angle,
+  physics::calculate_apparent_wind_simple,
+  ship::Ship,
+  simulation::{SimSettings, Simulation, DELTA_TIME}
+};
+
+/// How far a sensor's readings are from the truth
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SensorConfig {
+  /// Standard deviation of the random error in each measurement
+  pub noise: f64,
+  /// Error added to every measurement, such as from a badly calibrated sensor
+  pub bias: f64,
+  /// Time constant in seconds of the smoothing applied to measurements, 0.0 for none
+  pub damping: f64,
+  /// Times per second the display changes, holding the last reading in between, which must be more than zero
+  #[serde(deserialize_with = "deserialize_update_rate")]
+  pub update_rate: f64,
+}
+impl SensorConfig {
+  pub fn new(noise: f64, bias: f64, damping: f64, update_rate: f64) -> Self {
+    Self { noise, bias, damping, update_rate }
+  }
+
+  /// Readings exactly equal to the truth, every step
+  pub fn exact() -> Self {
+    Self::new(0.0, 0.0, 0.0, 1.0 / DELTA_TIME)
+  }
+
+  pub fn validate(&self) -> Result<(), String> {
+    if self.update_rate.is_nan() || self.update_rate <= 0.0 {
+      return Result::Err(String::from("Sensor update rate must be greater than zero"));
+    }
+    return Result::Ok(());
+  }
+}
+
+fn deserialize_update_rate<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+  let update_rate = f64::deserialize(deserializer)?;
+  SensorConfig::new(0.0, 0.0, 0.0, update_rate).validate().map_err(D::Error::custom)?;
+  return Result::Ok(update_rate);
+}
+
+/// A sensor for each of the instruments, with speeds in m/s and angles in radians
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InstrumentsConfig {
+  pub speed_through_water: SensorConfig,
+  pub speed_over_ground: SensorConfig,
+  pub compass_heading: SensorConfig,
+  pub apparent_wind_angle: SensorConfig,
+  pub apparent_wind_speed: SensorConfig,
+  pub true_wind_angle: SensorConfig,
+  pub true_wind_speed: SensorConfig,
+  pub heel: SensorConfig,
+}
+impl InstrumentsConfig {
+  pub fn validate(&self) -> Result<(), String> {
+    [
+      self.speed_through_water, self.speed_over_ground, self.compass_heading,
+      self.apparent_wind_angle, self.apparent_wind_speed, self.true_wind_angle, self.true_wind_speed, self.heel
+    ].iter().try_for_each(|sensor| sensor.validate())
+  }
+
+  pub fn exact() -> Self {
+    let exact = SensorConfig::exact();
+    Self {
+      speed_through_water: exact,
+      speed_over_ground: exact,
+      compass_heading: exact,
+      apparent_wind_angle: exact,
+      apparent_wind_speed: exact,
+      true_wind_angle: exact,
+      true_wind_speed: exact,
+      heel: exact,
+    }
+  }
+}
+impl Default for InstrumentsConfig {
+  /// Typical small boat electronics, with a paddle wheel log, GPS, fluxgate compass and masthead wind vane
+  fn default() -> Self {
+    Self {
+      speed_through_water: SensorConfig::new(0.05, 0.0, 1.0, 2.0),
+      speed_over_ground: SensorConfig::new(0.05, 0.0, 0.0, 1.0),
+      compass_heading: SensorConfig::new(0.01, 0.0, 0.5, 10.0),
+      apparent_wind_angle: SensorConfig::new(0.05, 0.0, 1.0, 4.0),
+      apparent_wind_speed: SensorConfig::new(0.2, 0.0, 1.0, 4.0),
+      true_wind_angle: SensorConfig::new(0.05, 0.0, 2.0, 1.0),
+      true_wind_speed: SensorConfig::new(0.2, 0.0, 2.0, 1.0),
+      heel: SensorConfig::new(0.01, 0.0, 0.5, 4.0),
+    }
+  }
+}
+
+/// What the instruments currently display, which is zero until their first update
+///
+/// Wind angles are where the wind comes from relative to the bow, positive to port. Heel is positive to starboard.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Readings {
+  pub speed_through_water: f64,
+  pub speed_over_ground: f64,
+  pub compass_heading: f64,
+  pub apparent_wind_angle: f64,
+  pub apparent_wind_speed: f64,
+  pub true_wind_angle: f64,
+  pub true_wind_speed: f64,
+  pub heel: f64,
+}
+impl Readings {
+  /// The exact values the instruments are trying to measure
+  pub fn truth(ship: &dyn Ship, settings: SimSettings) -> Self {
+    let kinematics = ship.kinematics();
+    let apparent_wind = calculate_apparent_wind_simple(kinematics.vel, settings.wind_angle, settings.wind_speed);
+    Self {
+      // A log only measures speed along the keel, through the water rather than over the ground
+      speed_through_water: (kinematics.vel - settings.current).rotate(-kinematics.heading).x,
+      speed_over_ground: kinematics.vel.magnitude(),
+      compass_heading: bound_angle(kinematics.heading),
+      apparent_wind_angle: bound_angle(apparent_wind.to_angle() + PI - kinematics.heading),
+      apparent_wind_speed: apparent_wind.magnitude(),
+      true_wind_angle: bound_angle(settings.wind_angle - kinematics.heading),
+      true_wind_speed: settings.wind_speed,
+      heel: ship.heel(settings.wind_angle, settings.wind_speed),
+    }
+  }
+}
+
+/// One instrument, turning true values into noisy, lagging readings
+#[derive(Debug, Clone, PartialEq)]
+struct Sensor {
+  config: SensorConfig,
+  /// Whether the value wraps around as an angle
+  angle: bool,
+  damped: Option<f64>,
+  reading: f64,
+  since_update: f64,
+}
+impl Sensor {
+  fn new(config: SensorConfig, angle: bool) -> Self {
+    Self { config, angle, damped: None, reading: 0.0, since_update: 0.0 }
+  }
+
+  /// Take a measurement, which always draws one random number so runs stay reproducible whatever the config
+  fn measure(&mut self, truth: f64, random: &mut StdRng) {
+    let measured = truth + self.config.bias + self.config.noise * gaussian(random);
+    let first = self.damped.is_none();
+    let damped = match self.damped {
+      Some(damped) if self.config.damping > 0.0 => {
+        let fraction = DELTA_TIME / (self.config.damping + DELTA_TIME);
+        let difference = if self.angle { bound_angle(measured - damped) } else { measured - damped };
+        damped + difference * fraction
+      }
+      _ => measured,
+    };
+    // Wrapping can change the last digit of an angle that is already in range, so only wrap those that aren't
+    let damped = if self.angle && !(-PI..PI).contains(&damped) { bound_angle(damped) } else { damped };
+    self.damped = Some(damped);
+
+    // Update on the step closest to when the display is due, and straight away the first time
+    self.since_update += DELTA_TIME;
+    if first || self.since_update >= 1.0 / self.config.update_rate - DELTA_TIME * 0.5 {
+      self.reading = damped;
+      self.since_update = 0.0;
+    }
+  }
+}
+
+/// A normally distributed random number with a standard deviation of one, by the Box-Muller transform
+fn gaussian(random: &mut StdRng) -> f64 {
+  let u1: f64 = 1.0 - random.gen::<f64>(); // Never zero, which has no logarithm
+  let u2: f64 = random.gen();
+  return (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+}
+
+/// The instruments fitted to one ship
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruments {
+  config: InstrumentsConfig,
+  speed_through_water: Sensor,
+  speed_over_ground: Sensor,
+  compass_heading: Sensor,
+  apparent_wind_angle: Sensor,
+  apparent_wind_speed: Sensor,
+  true_wind_angle: Sensor,
+  true_wind_speed: Sensor,
+  heel: Sensor,
+}
+impl Instruments {
+  pub fn new(config: InstrumentsConfig) -> Self {
+    Self {
+      config,
+      speed_through_water: Sensor::new(config.speed_through_water, false),
+      speed_over_ground: Sensor::new(config.speed_over_ground, false),
+      compass_heading: Sensor::new(config.compass_heading, true),
+      apparent_wind_angle: Sensor::new(config.apparent_wind_angle, true),
+      apparent_wind_speed: Sensor::new(config.apparent_wind_speed, false),
+      true_wind_angle: Sensor::new(config.true_wind_angle, true),
+      true_wind_speed: Sensor::new(config.true_wind_speed, false),
+      heel: Sensor::new(config.heel, true),
+    }
+  }
+
+  pub fn get_config(&self) -> InstrumentsConfig {
+    self.config
+  }
+
+  /// Measure the ship as it is after a step
+  pub fn update(&mut self, ship: &dyn Ship, settings: SimSettings, random: &mut StdRng) {
+    let truth = Readings::truth(ship, settings);
+    self.speed_through_water.measure(truth.speed_through_water, random);
+    self.speed_over_ground.measure(truth.speed_over_ground, random);
+    self.compass_heading.measure(truth.compass_heading, random);
+    self.apparent_wind_angle.measure(truth.apparent_wind_angle, random);
+    self.apparent_wind_speed.measure(truth.apparent_wind_speed, random);
+    self.true_wind_angle.measure(truth.true_wind_angle, random);
+    self.true_wind_speed.measure(truth.true_wind_speed, random);
+    self.heel.measure(truth.heel, random);
+  }
+
+  pub fn readings(&self) -> Readings {
+    Readings {
+      speed_through_water: self.speed_through_water.reading,
+      speed_over_ground: self.speed_over_ground.reading,
+      compass_heading: self.compass_heading.reading,
+      apparent_wind_angle: self.apparent_wind_angle.reading,
+      apparent_wind_speed: self.apparent_wind_speed.reading,
+      true_wind_angle: self.true_wind_angle.reading,
+      true_wind_speed: self.true_wind_speed.reading,
+      heel: self.heel.reading,
+    }
+  }
+}
+
+
+/// Fit instruments to a ship, or take them off if not enabled, using typical instruments if no config is given
+#[tauri::command(rename_all = "snake_case")]
+pub fn set_instruments(sim: State<Mutex<Simulation>>, index: usize, enabled: bool, config: Option<InstrumentsConfig>) -> Result<(), String> {
+  let mut sim = sim.lock().unwrap();
+  let config = match enabled {
+    true => Some(config.unwrap_or_default()),
+    false => None,
+  };
+  return sim.set_instruments(index, config);
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_instrument_readings(sim: State<Mutex<Simulation>>, index: usize) -> Option<Readings> {
+  let sim = sim.lock().unwrap();
+  return sim.readings(index);
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::f64::consts::PI;
+  use rand::{rngs::StdRng, SeedableRng};
+  use super::{Instruments, InstrumentsConfig, Readings, SensorConfig};
+  use crate::{
+    geometry::Vec2D,
+    ship::{AdjustableShip, Ship, ShipControls, ShipSpecs},
+    simulation::{SimSettings, Simulation, DELTA_TIME}
+  };
+
+  /// The same sensor for every instrument
+  fn all(sensor: SensorConfig) -> InstrumentsConfig {
+    let mut config = InstrumentsConfig::exact();
+    for field in [
+      &mut config.speed_through_water, &mut config.speed_over_ground, &mut config.compass_heading,
+      &mut config.apparent_wind_angle, &mut config.apparent_wind_speed, &mut config.true_wind_angle,
+      &mut config.true_wind_speed, &mut config.heel
+    ] {
+      *field = sensor;
+    }
+    return config;
+  }
+
+  fn dinghy() -> AdjustableShip {
+    AdjustableShip::new(ShipSpecs::default(), Vec2D::zeros(), Vec2D::new(1.0, 0.0), 0.05, 0.0, ShipControls::new(vec![1.0], 0.0))
+  }
+
+  #[test]
+  fn test_exact_readings_are_the_truth() {
+    let settings = SimSettings::new(PI * 0.5, 5.0).with_current(Vec2D::new(0.2, 0.1));
+    let mut random = StdRng::seed_from_u64(0);
+    let mut ship = dinghy();
+    let mut instruments = Instruments::new(InstrumentsConfig::exact());
+    for _ in 0..60 {
+      ship.update(settings.wind_angle, settings.wind_speed, settings.current, &[]);
+      instruments.update(&ship, settings, &mut random);
+      assert_eq!(instruments.readings(), Readings::truth(&ship, settings));
+    }
+  }
+
+  #[test]
+  fn test_readings_hold_between_updates() {
+    let settings = SimSettings::new(PI * 0.5, 5.0);
+    let mut random = StdRng::seed_from_u64(0);
+    let mut ship = dinghy();
+    let mut instruments = Instruments::new(all(SensorConfig::new(0.0, 0.0, 0.0, 1.0)));
+    let steps_per_update = (1.0 / DELTA_TIME).round() as usize;
+    let mut readings = Vec::new();
+    for _ in 0..=steps_per_update * 2 {
+      ship.update(settings.wind_angle, settings.wind_speed, settings.current, &[]);
+      instruments.update(&ship, settings, &mut random);
+      readings.push(instruments.readings().compass_heading);
+    }
+    // Once a second the reading jumps to where the ship is heading, and in between it stays put
+    assert!(readings[1..steps_per_update].iter().all(|reading| *reading == readings[0]));
+    assert_ne!(readings[steps_per_update], readings[0]);
+    assert!(readings[steps_per_update + 1..steps_per_update * 2].iter().all(|reading| *reading == readings[steps_per_update]));
+    assert_ne!(readings[steps_per_update * 2], readings[steps_per_update]);
+  }
+
+  #[test]
+  fn test_noise_bias_and_damping() {
+    let ship = dinghy();
+    let settings = SimSettings::new(PI * 0.5, 5.0);
+    let mut random = StdRng::seed_from_u64(0);
+
+    // Noisy readings spread around the biased truth
+    let mut instruments = Instruments::new(all(SensorConfig::new(0.1, 0.5, 0.0, 1.0 / DELTA_TIME)));
+    let errors: Vec<f64> = (0..3000).map(|_| {
+      instruments.update(&ship, settings, &mut random);
+      instruments.readings().true_wind_speed - settings.wind_speed
+    }).collect();
+    let mean = errors.iter().sum::<f64>() / errors.len() as f64;
+    let deviation = (errors.iter().map(|error| (error - mean).powi(2)).sum::<f64>() / errors.len() as f64).sqrt();
+    assert!((mean - 0.5).abs() < 0.01, "{}", mean);
+    assert!((deviation - 0.1).abs() < 0.01, "{}", deviation);
+
+    // Damped readings close most of the way to a change in a time constant, but not all of it
+    let mut instruments = Instruments::new(all(SensorConfig::new(0.0, 0.0, 1.0, 1.0 / DELTA_TIME)));
+    instruments.update(&ship, settings, &mut random);
+    let gust = SimSettings::new(PI * 0.5, 10.0);
+    for _ in 0..(1.0 / DELTA_TIME).round() as usize {
+      instruments.update(&ship, gust, &mut random);
+    }
+    let reading = instruments.readings().true_wind_speed;
+    assert!(reading > 5.0 + 5.0 * 0.55 && reading < 5.0 + 5.0 * 0.7, "{}", reading);
+  }
+
+  #[test]
+  fn test_update_rate_must_be_positive() {
+    let json = r#"{ "noise": 0.0, "bias": 0.0, "damping": 0.0, "update_rate": 0.0 }"#;
+    assert!(serde_json::from_str::<SensorConfig>(json).is_err());
+    let json = r#"{ "noise": 0.0, "bias": 0.0, "damping": 0.0, "update_rate": 2.0 }"#;
+    assert_eq!(serde_json::from_str::<SensorConfig>(json).unwrap(), SensorConfig::new(0.0, 0.0, 0.0, 2.0));
+
+    let mut sim = Simulation::new(0, 0.0, 5.0);
+    let inputs = sim.get_log().inputs.len();
+    let mut config = InstrumentsConfig::default();
+    config.heel.update_rate = -1.0;
+    assert!(sim.set_instruments(0, Some(config)).is_err());
+    assert!(sim.readings(0).is_none());
+    assert_eq!(sim.get_log().inputs.len(), inputs);
+  }
+}
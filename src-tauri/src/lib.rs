@@ -0,0 +1,174 @@
+use events::{Publisher, DEFAULT_EVENT_RATE};
+use nmea::NmeaOutput;
+use recording::{Recorder, DEFAULT_CAPACITY};
+use remote::{RemoteServer, DEFAULT_REMOTE_PORT};
+use runner::Runner;
+use snapshots::{Snapshots, AUTO_SNAPSHOT_CAPACITY, AUTO_SNAPSHOT_INTERVAL};
+use simulation::Simulation;
+use std::{net::TcpListener, sync::Mutex};
+
+pub mod catamaran;
+pub mod determinism;
+pub mod geo;
+pub mod geometry;
+pub mod ghost;
+pub mod instruments;
+pub mod simulation;
+pub mod drawing;
+pub mod events;
+pub mod gym;
+pub mod mooring;
+pub mod nmea;
+pub mod physics;
+pub mod recording;
+pub mod remote;
+pub mod runner;
+pub mod propulsion;
+pub mod scenario;
+pub mod scripting;
+pub mod ship;
+pub mod snapshots;
+pub mod spatial;
+pub mod telemetry;
+pub mod towing;
+
+
+/// Run the app, or a command line tool if one was asked for in the arguments
+pub fn run() {
+  let args: Vec<String> = std::env::args().collect();
+  if let Some(index) = args.iter().position(|arg| arg == "--verify") {
+    let path = args.get(index + 1).map(String::as_str).unwrap_or_default();
+    match determinism::verify_file(path) {
+      Ok(report) if report.mismatch.is_none() => println!("Verified {} steps and {} checkpoints", report.steps, report.checkpoints),
+      Ok(report) => {
+        eprintln!("State differs from the log at step {}", report.mismatch.unwrap());
+        std::process::exit(1);
+      }
+      Err(message) => {
+        eprintln!("{}", message);
+        std::process::exit(1);
+      }
+    }
+    return;
+  }
+  if args.iter().any(|arg| arg == "--export") {
+    match telemetry::export_from_args(&args[1..]) {
+      Ok(message) => println!("{}", message),
+      Err(message) => {
+        eprintln!("{}", message);
+        std::process::exit(1);
+      }
+    }
+    return;
+  }
+  if let Some(index) = args.iter().position(|arg| arg == "--gym-server") {
+    let port = args.get(index + 1).and_then(|port| port.parse::<u16>().ok()).unwrap_or(gym::DEFAULT_PORT);
+    let served = TcpListener::bind(("127.0.0.1", port)).and_then(|listener| {
+      println!("Serving environments on port {}", port);
+      return gym::serve(listener);
+    });
+    if let Err(error) = served {
+      eprintln!("{}", error);
+      std::process::exit(1);
+    }
+    return;
+  }
+
+  tauri::Builder::default()
+  .manage(
+    Mutex::new(Simulation::new(12345, 0.0, 5.0))
+  )
+  .manage(
+    Mutex::new(Runner::new())
+  )
+  .manage(
+    Mutex::new(Publisher::new(DEFAULT_EVENT_RATE))
+  )
+  .manage(
+    Mutex::new(Recorder::new(DEFAULT_CAPACITY, 1))
+  )
+  .manage(
+    Mutex::new(Snapshots::new(AUTO_SNAPSHOT_INTERVAL, AUTO_SNAPSHOT_CAPACITY))
+  )
+  .manage(
+    Mutex::new(RemoteServer::new())
+  )
+  .manage(
+    Mutex::new(NmeaOutput::new())
+  )
+  .setup(move |app| {
+    runner::spawn(app.handle());
+    if let Some(index) = args.iter().position(|arg| arg == "--remote") {
+      let port = args.get(index + 1).and_then(|port| port.parse::<u16>().ok()).unwrap_or(DEFAULT_REMOTE_PORT);
+      remote::start(&app.handle(), port)?;
+    }
+    Ok(())
+  })
+  .invoke_handler(tauri::generate_handler![
+    simulation::reset_simulation,
+    simulation::step_simulation,
+    simulation::run_simulation_steps,
+    runner::start_simulation,
+    runner::pause_simulation,
+    runner::set_simulation_speed,
+    runner::get_run_state,
+    events::set_event_rate,
+    events::select_ship,
+    recording::set_recording,
+    recording::get_recording_info,
+    recording::seek_replay,
+    recording::play_replay,
+    recording::pause_replay,
+    recording::set_replay_speed,
+    recording::stop_replay,
+    recording::save_recording,
+    snapshots::take_snapshot,
+    snapshots::restore_snapshot,
+    snapshots::rewind_simulation,
+    snapshots::delete_snapshot,
+    snapshots::list_snapshots,
+    telemetry::export_telemetry,
+    geo::export_tracks,
+    determinism::save_input_log,
+    determinism::verify_input_log,
+    ghost::import_gpx_track,
+    instruments::set_instruments,
+    instruments::get_instrument_readings,
+    scripting::load_scenario_script,
+    scripting::attach_controller,
+    scripting::detach_controller,
+    scripting::clear_scripts,
+    remote::start_remote_server,
+    remote::stop_remote_server,
+    remote::get_remote_server,
+    nmea::start_nmea_output,
+    nmea::stop_nmea_output,
+    nmea::get_nmea_output,
+    simulation::get_population,
+    simulation::get_sim_settings,
+    simulation::set_sim_settings,
+    simulation::get_ship,
+    simulation::get_ship_id,
+    simulation::set_ship_controls,
+    simulation::set_ship_throttle,
+    simulation::drop_anchor,
+    simulation::weigh_anchor,
+    simulation::moor_ship,
+    simulation::cast_off,
+    simulation::get_ship_lines,
+    simulation::get_ship_line_shapes,
+    simulation::get_marks,
+    simulation::add_ship_line,
+    simulation::tow_ship,
+    simulation::raft_ships,
+    simulation::release_ship_lines,
+    scenario::load_scenario,
+    scenario::load_bundled_scenario,
+    scenario::list_bundled_scenarios,
+    scenario::get_scenario,
+    ship::debug_ship_physics,
+    physics::debug_coefficients,
+  ])
+  .run(tauri::generate_context!())
+  .expect("error while running tauri application");
+}
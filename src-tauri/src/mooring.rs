@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  geometry::Vec2D,
+  physics::{Force, ForceName, Kinematics}
+};
+
+// Like every force on a ship, tensions are impulses in kg m/s given each step rather than newtons, so the line
+// constants are 1 / DELTA_TIME (30) times smaller than the same constants in SI units
+pub const LINE_STIFFNESS: f64 = 0.5; // Tension per meter the line is stretched past its length, about 15 N/m
+pub const LINE_DAMPING: f64 = 5.0; // Tension per m/s the line is being stretched, about 150 N s/m, so the ship doesn't bounce forever
+pub const ANCHOR_HOLDING_FORCE: f64 = 2.0; // Tension the anchor can take before it drags along the bottom, about 60 N
+
+/// Check a line or rode can be paid out to the given length
+pub fn check_line_length(length: f64) -> Result<(), String> {
+  if length.is_finite() && length >= 0.0 {
+    return Result::Ok(());
+  }
+  return Result::Err(String::from("Line length must be a number that isn't negative"));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TetherKind {
+  Anchor,
+  Mooring,
+}
+
+/// A line holding a ship to a fixed point, which only pulls when it is taut
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tether {
+  pub kind: TetherKind,
+  /// Where the line is fixed, on the bottom or ashore
+  pub anchor_point: Vec2D,
+  /// Where the line is made fast on the ship, relative to the center of the ship
+  pub attachment: Vec2D,
+  pub length: f64,
+  /// Tension the fixed end can take before it drags, or None if it never moves
+  pub holding_force: Option<f64>,
+  pub dragging: bool,
+}
+impl Tether {
+  pub fn anchor(anchor_point: Vec2D, attachment: Vec2D, rode_length: f64) -> Self {
+    Self {
+      kind: TetherKind::Anchor,
+      anchor_point, attachment,
+      length: rode_length,
+      holding_force: Some(ANCHOR_HOLDING_FORCE),
+      dragging: false,
+    }
+  }
+
+  pub fn mooring(anchor_point: Vec2D, attachment: Vec2D, line_length: f64) -> Self {
+    Self {
+      kind: TetherKind::Mooring,
+      anchor_point, attachment,
+      length: line_length,
+      holding_force: None,
+      dragging: false,
+    }
+  }
+
+  /// The tension in the line, which is zero while the line is slack
+  pub fn tension(&self, kinematics: Kinematics) -> f64 {
+    let attachment_loc = kinematics.point(self.attachment);
+    let line = attachment_loc - self.anchor_point;
+    let stretch = line.magnitude() - self.length;
+    if stretch <= 0.0 {
+      return 0.0;
+    }
+    let stretch_rate = kinematics.point_velocity(self.attachment).dot(line.unit());
+    return f64::max(LINE_STIFFNESS * stretch + LINE_DAMPING * stretch_rate, 0.0);
+  }
+
+  /// The pull of the line on the ship, if it is taut
+  pub fn force(&self, kinematics: Kinematics) -> Option<Force> {
+    let tension = self.tension(kinematics);
+    if tension == 0.0 {
+      return None;
+    }
+    let attachment_loc = kinematics.point(self.attachment);
+    let pull = (self.anchor_point - attachment_loc).unit().scale(tension);
+    let name = match self.kind {
+      TetherKind::Anchor => ForceName::AnchorRode,
+      TetherKind::Mooring => ForceName::MooringLine,
+    };
+    return Some(Force::new(name, attachment_loc, pull));
+  }
+
+  /// Drag the fixed end towards the ship if the line pulls harder than it can hold
+  pub fn slip(&mut self, kinematics: Kinematics) {
+    let holding_force = match self.holding_force {
+      Some(holding_force) => holding_force,
+      None => return,
+    };
+    let tension = self.tension(kinematics);
+    self.dragging = tension > holding_force;
+    if self.dragging {
+      // Move to where the line would be stretched just enough to match the holding force
+      let attachment_loc = kinematics.point(self.attachment);
+      let held_length = self.length + holding_force / LINE_STIFFNESS;
+      self.anchor_point = attachment_loc + (self.anchor_point - attachment_loc).unit().scale(held_length);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Tether, ANCHOR_HOLDING_FORCE, LINE_STIFFNESS};
+  use crate::{
+    geometry::Vec2D,
+    physics::Kinematics,
+    simulation::Simulation
+  };
+
+  /// A ship lying still, with the rode stretched the given distance past its length
+  fn stretched(stretch: f64) -> (Tether, Kinematics) {
+    let tether = Tether::anchor(Vec2D::zeros(), Vec2D::zeros(), 10.0);
+    let kinematics = Kinematics::new(Vec2D::new(10.0 + stretch, 0.0), Vec2D::zeros(), 0.0, 0.0);
+    return (tether, kinematics);
+  }
+
+  #[test]
+  fn test_anchor_holds_below_its_holding_force() {
+    let (mut tether, kinematics) = stretched(ANCHOR_HOLDING_FORCE / LINE_STIFFNESS * 0.75);
+    assert!(tether.tension(kinematics) < ANCHOR_HOLDING_FORCE);
+    tether.slip(kinematics);
+    assert!(!tether.dragging);
+    assert_eq!(tether.anchor_point, Vec2D::zeros());
+  }
+
+  #[test]
+  fn test_anchor_drags_above_its_holding_force() {
+    let (mut tether, kinematics) = stretched(ANCHOR_HOLDING_FORCE / LINE_STIFFNESS * 1.5);
+    tether.slip(kinematics);
+    assert!(tether.dragging);
+    // It comes to rest where the rode pulls with just the holding force
+    assert!(tether.anchor_point.x > 0.0);
+    assert!((tether.tension(kinematics) - ANCHOR_HOLDING_FORCE).abs() < 1e-9);
+  }
+
+  #[test]
+  fn test_bad_lines_are_refused_and_not_logged() {
+    let mut sim = Simulation::new(1, 0.0, 5.0);
+    let logged = sim.get_log().inputs.len();
+    assert!(sim.drop_anchor(0, -1.0).is_err());
+    assert!(sim.drop_anchor(0, f64::NAN).is_err());
+    assert!(sim.drop_anchor(99, 10.0).is_err());
+    assert!(sim.moor_ship(0, Vec2D::new(f64::INFINITY, 0.0), 10.0).is_err());
+    assert!(sim.moor_ship(99, Vec2D::zeros(), 10.0).is_err());
+    assert_eq!(sim.get_log().inputs.len(), logged);
+    assert!(sim.get_ship(0).unwrap().tethers().is_empty());
+
+    assert!(sim.drop_anchor(0, 10.0).is_ok());
+    assert_eq!(sim.get_log().inputs.len(), logged + 1);
+    assert_eq!(sim.get_ship(0).unwrap().tethers().len(), 1);
+  }
+}
@@ -0,0 +1,316 @@
+use std::{
+  io::Write,
+  net::{TcpListener, TcpStream, UdpSocket},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex
+  },
+  thread::{self, JoinHandle},
+  time::{Duration, Instant}
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::{
+  events::Publisher,
+  geo::{format_timestamp, GeoOrigin},
+  physics::{calculate_apparent_wind, Kinematics},
+  simulation::{SimSettings, Simulation}
+};
+
+pub const DEFAULT_NMEA_RATE: f64 = 1.0; // Sentences of each kind per second
+const KNOTS_PER_METRE_PER_SECOND: f64 = 3600.0 / 1852.0;
+const KMH_PER_METRE_PER_SECOND: f64 = 3.6;
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(1); // Clients that can't keep up for this long are dropped
+
+/// Where the sentences are sent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum NmeaTransport {
+  /// Datagrams to an address such as 127.0.0.1:10110, which may be a broadcast address
+  Udp { address: String },
+  /// A stream to every client connected to this port on localhost
+  Tcp { port: u16 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NmeaConfig {
+  pub transport: NmeaTransport,
+  /// Where the simulation is in the world, for the position and time sentences
+  pub origin: GeoOrigin,
+  /// The ship to report on, or the selected ship if not given
+  pub ship: Option<usize>,
+  pub rate: Option<f64>,
+}
+
+/// Bearing in degrees clockwise from north, as used by compasses, of an angle anticlockwise from the x axis
+fn to_bearing(angle: f64) -> f64 {
+  (90.0 - angle.to_degrees()).rem_euclid(360.0)
+}
+
+/// Degrees clockwise from the bow of the direction something comes from
+fn relative_bearing(from_angle: f64, heading: f64) -> f64 {
+  (heading - from_angle).to_degrees().rem_euclid(360.0)
+}
+
+/// A latitude or longitude as degrees and decimal minutes, followed by its hemisphere
+fn format_coordinate(degrees: f64, degree_digits: usize, positive: char, negative: char) -> String {
+  let hemisphere = if degrees < 0.0 { negative } else { positive };
+  // Counted in the ten thousandths of a minute that are printed, so minutes that round up to 60 carry into the degrees
+  let units = (degrees.abs() * 60.0 * 10_000.0).round() as u64;
+  let whole = units / 600_000;
+  let minutes = (units % 600_000) as f64 / 10_000.0;
+  return format!("{:0width$}{:07.4},{}", whole, minutes, hemisphere, width = degree_digits);
+}
+
+/// Add the $ and the checksum, which is the XOR of every character between them
+pub fn sentence(body: &str) -> String {
+  let checksum = body.bytes().fold(0u8, |checksum, byte| checksum ^ byte);
+  return format!("${}*{:02X}\r\n", body, checksum);
+}
+
+/// The sentences for a ship's state: RMC, VTG, HDG, VHW and MWV for both apparent and true wind
+pub fn sentences(kinematics: Kinematics, settings: SimSettings, origin: &GeoOrigin, step: u64) -> String {
+  let timestamp = format_timestamp(origin.time_at(step));
+  // From YYYY-MM-DDTHH:MM:SS.sssZ to HHMMSS.ss and DDMMYY
+  let time = format!("{}{}{}", &timestamp[11..13], &timestamp[14..16], &timestamp[17..22]);
+  let date = format!("{}{}{}", &timestamp[8..10], &timestamp[5..7], &timestamp[2..4]);
+  let (latitude, longitude) = origin.to_geo(kinematics.loc);
+
+  let speed = kinematics.vel.magnitude();
+  let course = to_bearing(kinematics.vel.to_angle());
+  let heading = to_bearing(kinematics.heading);
+  // A log only measures speed along the keel, through the water rather than over the ground
+  let water_speed = (kinematics.vel - settings.current).rotate(-kinematics.heading).x;
+
+  let apparent_wind = calculate_apparent_wind(
+    kinematics.vel, kinematics.rot_vel, kinematics.heading, 0.0, settings.wind_angle, settings.wind_speed
+  );
+  let apparent_wind_angle = relative_bearing(apparent_wind.to_angle() + std::f64::consts::PI, kinematics.heading);
+  let true_wind_angle = relative_bearing(settings.wind_angle, kinematics.heading);
+
+  let mut out = String::new();
+  out.push_str(&sentence(&format!(
+    "GPRMC,{},A,{},{},{:.1},{:.1},{},,,A",
+    time,
+    format_coordinate(latitude, 2, 'N', 'S'),
+    format_coordinate(longitude, 3, 'E', 'W'),
+    speed * KNOTS_PER_METRE_PER_SECOND,
+    course,
+    date
+  )));
+  out.push_str(&sentence(&format!(
+    "GPVTG,{:.1},T,,M,{:.1},N,{:.1},K,A",
+    course, speed * KNOTS_PER_METRE_PER_SECOND, speed * KMH_PER_METRE_PER_SECOND
+  )));
+  // There is no magnetic variation in the simulation, so magnetic and true headings are the same
+  out.push_str(&sentence(&format!("IIHDG,{:.1},0.0,E,0.0,E", heading)));
+  out.push_str(&sentence(&format!(
+    "IIVHW,{:.1},T,{:.1},M,{:.1},N,{:.1},K",
+    heading, heading, water_speed * KNOTS_PER_METRE_PER_SECOND, water_speed * KMH_PER_METRE_PER_SECOND
+  )));
+  out.push_str(&sentence(&format!("IIMWV,{:.1},R,{:.1},M,A", apparent_wind_angle, apparent_wind.magnitude())));
+  out.push_str(&sentence(&format!("IIMWV,{:.1},T,{:.1},M,A", true_wind_angle, settings.wind_speed)));
+  return out;
+}
+
+/// Sends sentences to wherever the config says
+enum Sender {
+  Udp { socket: UdpSocket, address: String },
+  Tcp { listener: TcpListener, clients: Vec<TcpStream> },
+}
+impl Sender {
+  fn new(transport: &NmeaTransport) -> Result<Self, String> {
+    match transport {
+      NmeaTransport::Udp { address } => {
+        let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+        socket.set_broadcast(true).map_err(|e| e.to_string())?;
+        return Result::Ok(Sender::Udp { socket, address: address.clone() });
+      }
+      NmeaTransport::Tcp { port } => {
+        let listener = TcpListener::bind(("127.0.0.1", *port)).map_err(|e| e.to_string())?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        return Result::Ok(Sender::Tcp { listener, clients: Vec::new() });
+      }
+    }
+  }
+
+  fn send(&mut self, sentences: &str) {
+    match self {
+      Sender::Udp { socket, address } => {
+        // Nothing may be listening, which is fine
+        let _ = socket.send_to(sentences.as_bytes(), address.as_str());
+      }
+      Sender::Tcp { listener, clients } => {
+        // Take everyone who has connected since the last sentences, until accepting would block
+        while let Ok((stream, _)) = listener.accept() {
+          // Accepted streams may inherit the listener's non-blocking mode, which would drop them on a full buffer
+          let blocking = stream.set_nonblocking(false).and_then(|_| stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT)));
+          if blocking.is_ok() {
+            clients.push(stream);
+          }
+        }
+        clients.retain_mut(|client| client.write_all(sentences.as_bytes()).is_ok());
+      }
+    }
+  }
+}
+
+/// The running output, if there is one
+pub struct NmeaOutput {
+  config: Option<NmeaConfig>,
+  stop: Arc<AtomicBool>,
+  thread: Option<JoinHandle<()>>,
+}
+impl NmeaOutput {
+  pub fn new() -> Self {
+    Self { config: None, stop: Arc::new(AtomicBool::new(false)), thread: None }
+  }
+
+  /// Stop the output and wait for it to finish, so its socket is closed
+  fn stop(&mut self) {
+    self.stop.store(true, Ordering::Relaxed);
+    if let Some(thread) = self.thread.take() {
+      thread.thread().unpark();
+      let _ = thread.join();
+    }
+    self.config = None;
+  }
+
+  /// Replace any output already running with one sending whatever `next_sentences` gives, `rate` times a second
+  fn restart(&mut self, config: NmeaConfig, rate: f64, next_sentences: impl FnMut() -> Option<String> + Send + 'static) -> Result<(), String> {
+    // The old output has to let go of a TCP port before it can be bound again
+    self.stop();
+    let sender = Sender::new(&config.transport)?;
+    self.stop = Arc::new(AtomicBool::new(false));
+    self.thread = Some(spawn(sender, rate, self.stop.clone(), next_sentences));
+    self.config = Some(config);
+    return Result::Ok(());
+  }
+}
+impl Default for NmeaOutput {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Start sending sentences for the ship in the background, replacing any output already running
+pub fn start(app: &AppHandle, config: NmeaConfig) -> Result<(), String> {
+  let rate = config.rate.unwrap_or(DEFAULT_NMEA_RATE);
+  if !(rate > 0.0 && rate.is_finite()) {
+    return Result::Err(String::from("Rate must be greater than zero"));
+  }
+  let (source, ship, origin) = (app.clone(), config.ship, config.origin);
+  let next_sentences = move || {
+    let ship = ship.or_else(|| source.state::<Mutex<Publisher>>().lock().unwrap().selected());
+    let sim = source.state::<Mutex<Simulation>>();
+    let sim = sim.lock().unwrap();
+    let ship = sim.get_ship(ship?)?;
+    return Some(sentences(ship.kinematics(), sim.get_settings(), &origin, sim.get_step()));
+  };
+  let output = app.state::<Mutex<NmeaOutput>>();
+  let mut output = output.lock().unwrap();
+  return output.restart(config, rate, next_sentences);
+}
+
+fn spawn(mut sender: Sender, rate: f64, stop: Arc<AtomicBool>, mut next_sentences: impl FnMut() -> Option<String> + Send + 'static) -> JoinHandle<()> {
+  let interval = Duration::from_secs_f64(1.0 / rate);
+  return thread::spawn(move || {
+    while !stop.load(Ordering::Relaxed) {
+      if let Some(sentences) = next_sentences() {
+        sender.send(&sentences);
+      }
+      // Parked rather than asleep, so stopping doesn't have to wait out the interval
+      let due = Instant::now() + interval;
+      while !stop.load(Ordering::Relaxed) && Instant::now() < due {
+        thread::park_timeout(due.saturating_duration_since(Instant::now()));
+      }
+    }
+  });
+}
+
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn start_nmea_output(app: AppHandle, config: NmeaConfig) -> Result<(), String> {
+  return start(&app, config);
+}
+
+#[tauri::command]
+pub fn stop_nmea_output(output: State<Mutex<NmeaOutput>>) {
+  let mut output = output.lock().unwrap();
+  output.stop();
+}
+
+#[tauri::command]
+pub fn get_nmea_output(output: State<Mutex<NmeaOutput>>) -> Option<NmeaConfig> {
+  let output = output.lock().unwrap();
+  return output.config.clone();
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::f64::consts::PI;
+  use std::{
+    io::{BufRead, BufReader},
+    net::{TcpListener, TcpStream},
+    time::Duration
+  };
+  use super::{format_coordinate, sentence, sentences, NmeaConfig, NmeaOutput, NmeaTransport};
+  use crate::{geo::GeoOrigin, geometry::Vec2D, physics::Kinematics, simulation::SimSettings};
+
+  #[test]
+  fn test_checksum() {
+    assert_eq!(
+      sentence("GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W"),
+      "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A\r\n"
+    );
+  }
+
+  #[test]
+  fn test_coordinates() {
+    assert_eq!(format_coordinate(48.1173, 2, 'N', 'S'), "4807.0380,N");
+    assert_eq!(format_coordinate(-3.5, 3, 'E', 'W'), "00330.0000,W");
+    // Just short of a whole degree rounds up into it rather than printing 60 minutes
+    assert_eq!(format_coordinate(49.0 + 59.99999 / 60.0, 2, 'N', 'S'), "5000.0000,N");
+    assert_eq!(format_coordinate(-(1.0 + 59.99994 / 60.0), 3, 'E', 'W'), "00159.9999,W");
+  }
+
+  #[test]
+  fn test_sentences() {
+    // Sailing north at 2 m/s with the wind from the east
+    let kinematics = Kinematics::new(Vec2D::zeros(), Vec2D::new(0.0, 2.0), 0.0, PI / 2.0);
+    let settings = SimSettings::new(0.0, 5.0);
+    let origin = GeoOrigin { latitude: 50.0, longitude: -1.0, start_time: 1_700_000_000.0 };
+    let out = sentences(kinematics, settings, &origin, 30);
+    let lines: Vec<&str> = out.lines().collect();
+    assert!(lines[0].starts_with("$GPRMC,221321.00,A,5000.0000,N,00100.0000,W,3.9,0.0,141123,,,A*"));
+    assert!(lines[2].starts_with("$IIHDG,0.0,"));
+    assert!(lines[3].starts_with("$IIVHW,0.0,T,0.0,M,3.9,N,7.2,K*"));
+    // The true wind is on the starboard beam, and sailing pulls the apparent wind forward
+    assert!(lines[5].starts_with("$IIMWV,90.0,T,5.0,M,A*"));
+    assert!(lines[4].starts_with("$IIMWV,68.2,R,5.4,M,A*"));
+  }
+
+  #[test]
+  fn test_restart_on_same_port() {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let config = NmeaConfig {
+      transport: NmeaTransport::Tcp { port },
+      origin: GeoOrigin { latitude: 50.0, longitude: -1.0, start_time: 1_700_000_000.0 },
+      ship: None,
+      rate: Some(20.0),
+    };
+    let mut output = NmeaOutput::new();
+    output.restart(config.clone(), 20.0, || Some(String::from("first\r\n"))).unwrap();
+    // The first output is still listening on the port when it is replaced
+    output.restart(config, 20.0, || Some(String::from("second\r\n"))).unwrap();
+
+    let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).unwrap();
+    assert_eq!(line, "second\r\n");
+    output.stop();
+  }
+}
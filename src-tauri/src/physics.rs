@@ -1,22 +1,174 @@
-use std::f64::consts::PI;
+use std::{f64::consts::PI, fmt};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::geometry::{invert_angle, Vec2D};
+use crate::{
+  geometry::{bound_angle, invert_angle, Vec2D},
+  simulation::DELTA_TIME
+};
 
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+/// One of the two hulls of a multihull
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HullSide {
+  Port,
+  Starboard,
+}
+impl fmt::Display for HullSide {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      HullSide::Port => write!(f, "Port"),
+      HullSide::Starboard => write!(f, "Starboard"),
+    }
+  }
+}
+
+/// What a force comes from, so it can be told apart when debugging and drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ForceName {
+  SailLift(usize),
+  SailDrag(usize),
+  ForeKeelLift,
+  ForeKeelDrag,
+  AftKeelLift,
+  AftKeelDrag,
+  RudderLift,
+  RudderDrag,
+  BowDrag,
+  SternDrag,
+  DaggerboardLift(HullSide),
+  DaggerboardDrag(HullSide),
+  HullRudderLift(HullSide),
+  HullRudderDrag(HullSide),
+  HullBowDrag(HullSide),
+  HullSternDrag(HullSide),
+  PropellerThrust,
+  PropWalk,
+  AnchorRode,
+  MooringLine,
+  ShipLine,
+  // Not forces, but drawn alongside them
+  Wind,
+  Velocity,
+  ApparentWind,
+  Rotation,
+}
+impl fmt::Display for ForceName {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ForceName::SailLift(index) => write!(f, "Sail {} Lift", index),
+      ForceName::SailDrag(index) => write!(f, "Sail {} Drag", index),
+      ForceName::ForeKeelLift => write!(f, "Fore Keel Lift"),
+      ForceName::ForeKeelDrag => write!(f, "Fore Keel Drag"),
+      ForceName::AftKeelLift => write!(f, "Aft Keel Lift"),
+      ForceName::AftKeelDrag => write!(f, "Aft Keel Drag"),
+      ForceName::RudderLift => write!(f, "Rudder Lift"),
+      ForceName::RudderDrag => write!(f, "Rudder Drag"),
+      ForceName::BowDrag => write!(f, "Bow Drag"),
+      ForceName::SternDrag => write!(f, "Stern Drag"),
+      ForceName::DaggerboardLift(side) => write!(f, "{} Daggerboard Lift", side),
+      ForceName::DaggerboardDrag(side) => write!(f, "{} Daggerboard Drag", side),
+      ForceName::HullRudderLift(side) => write!(f, "{} Rudder Lift", side),
+      ForceName::HullRudderDrag(side) => write!(f, "{} Rudder Drag", side),
+      ForceName::HullBowDrag(side) => write!(f, "{} Bow Drag", side),
+      ForceName::HullSternDrag(side) => write!(f, "{} Stern Drag", side),
+      ForceName::PropellerThrust => write!(f, "Propeller Thrust"),
+      ForceName::PropWalk => write!(f, "Prop Walk"),
+      ForceName::AnchorRode => write!(f, "Anchor Rode"),
+      ForceName::MooringLine => write!(f, "Mooring Line"),
+      ForceName::ShipLine => write!(f, "Ship Line"),
+      ForceName::Wind => write!(f, "Wind"),
+      ForceName::Velocity => write!(f, "Velocity"),
+      ForceName::ApparentWind => write!(f, "Apparent Wind"),
+      ForceName::Rotation => write!(f, "Rotation"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub struct Force {
-  pub name: String,
+  pub name: ForceName,
   pub loc: Vec2D,
   pub vec: Vec2D,
 }
 impl Force {
-  pub fn new(name: String, loc: Vec2D, vec: Vec2D) -> Self {
+  pub fn new(name: ForceName, loc: Vec2D, vec: Vec2D) -> Self {
     Self { name, loc, vec }
   }
 }
 
+/// Somewhere to put the forces on a ship as they are worked out
+pub trait ForceSink {
+  fn add(&mut self, name: ForceName, loc: Vec2D, vec: Vec2D);
+
+  fn add_force(&mut self, force: Force) {
+    self.add(force.name, force.loc, force.vec);
+  }
+}
+
+/// Keeps every force, for debugging and drawing
+impl ForceSink for Vec<Force> {
+  fn add(&mut self, name: ForceName, loc: Vec2D, vec: Vec2D) {
+    self.push(Force::new(name, loc, vec));
+  }
+}
+
+/// The total force and torque on a body, added up as the forces are worked out without keeping them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NetForce {
+  center_of_mass: Vec2D,
+  pub vec: Vec2D,
+  pub torque: f64,
+}
+impl NetForce {
+  pub fn new(center_of_mass: Vec2D) -> Self {
+    Self { center_of_mass, vec: Vec2D::zeros(), torque: 0.0 }
+  }
+}
+impl ForceSink for NetForce {
+  fn add(&mut self, _name: ForceName, loc: Vec2D, vec: Vec2D) {
+    self.vec = self.vec + vec;
+    // Only the part of the force across the line to the center of mass turns the body
+    self.torque += (loc - self.center_of_mass).cross(vec);
+  }
+}
+
+/// The motion of a rigid body in the plane
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Kinematics {
+  pub loc: Vec2D,
+  pub vel: Vec2D,
+  pub rot_vel: f64,
+  pub heading: f64,
+}
+impl Kinematics {
+  pub fn new(loc: Vec2D, vel: Vec2D, rot_vel: f64, heading: f64) -> Self {
+    Self { loc, vel, rot_vel, heading }
+  }
+
+  /// The location of a point on the body, given its offset from the center in the body's frame
+  pub fn point(&self, offset: Vec2D) -> Vec2D {
+    self.loc + offset.rotate(self.heading)
+  }
+
+  /// The velocity of a point on the body, given its offset from the center in the body's frame
+  pub fn point_velocity(&self, offset: Vec2D) -> Vec2D {
+    let world_offset = offset.rotate(self.heading);
+    self.vel + Vec2D::new(-self.rot_vel * world_offset.y, self.rot_vel * world_offset.x)
+  }
+
+  /// Apply the net force to the body and advance it by one time step
+  pub fn apply_net_force(&mut self, net: NetForce, mass: f64) {
+    let inverse_mass = 1.0 / mass;
+    self.vel = self.vel + net.vec.scale(inverse_mass);
+    // Just assume the ship is a point mass even though it obviously isn't
+    self.rot_vel += net.torque * inverse_mass;
+
+    self.loc = self.loc + self.vel.scale(DELTA_TIME);
+    self.heading = bound_angle(self.heading + self.rot_vel * DELTA_TIME);
+  }
+}
+
 
 pub fn calculate_aero_force_vecs(aoa: f64, area: f64, density: f64, velocity: Vec2D) -> (Vec2D, Vec2D) {
   let lift_magnitude = calculate_lift(aoa, area, density, velocity.magnitude());
@@ -0,0 +1,131 @@
+use std::f64::consts::PI;
+
+use serde::Serialize;
+
+use crate::{
+  geometry::Vec2D,
+  physics::{ForceName, ForceSink, Kinematics},
+  simulation::DELTA_TIME
+};
+
+pub const REVERSE_EFFICIENCY: f64 = 0.6; // Propellers are shaped to push forward, so they are weaker in reverse
+pub const FORWARD_PROP_WALK: f64 = 0.25; // Prop walk is much weaker with the propeller turning ahead
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct EngineSpecs {
+  /// Thrust at full throttle ahead, as an impulse in kg m/s given each step like the other forces on the hull, so 1.0 is
+  /// about 30 N
+  pub max_thrust: f64,
+  /// Sideways push on the stern in reverse, as a fraction of the thrust
+  pub prop_walk: f64,
+  /// Location of the propeller along the hull, relative to the center
+  pub prop_offset: f64,
+  /// Fuel tank size, in liters
+  pub fuel_capacity: f64,
+  /// Fuel burned at full throttle, in liters per second
+  pub fuel_consumption: f64,
+}
+impl EngineSpecs {
+  pub fn new(max_thrust: f64, prop_walk: f64, prop_offset: f64, fuel_capacity: f64, fuel_consumption: f64) -> Self {
+    Self { max_thrust, prop_walk, prop_offset, fuel_capacity, fuel_consumption }
+  }
+
+  /// A small outboard, as carried by a sailboat for getting in and out of harbour
+  pub fn auxiliary(prop_offset: f64) -> Self {
+    Self::new(0.3, 0.1, prop_offset, 12.0, 0.0005)
+  }
+
+  /// An inboard diesel for a launch or committee boat
+  pub fn inboard(prop_offset: f64) -> Self {
+    Self::new(1.0, 0.15, prop_offset, 100.0, 0.002)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Engine {
+  pub specs: EngineSpecs,
+  /// Fuel remaining, in liters
+  pub fuel: f64,
+}
+impl Engine {
+  /// An engine with a full tank
+  pub fn new(specs: EngineSpecs) -> Self {
+    Self { specs, fuel: specs.fuel_capacity }
+  }
+
+  /// The thrust (after accounting for direction) the engine produces at the given throttle, or zero when out of fuel
+  pub fn thrust(&self, throttle: f64) -> f64 {
+    if self.fuel <= 0.0 {
+      return 0.0;
+    }
+    let efficiency = if throttle < 0.0 { REVERSE_EFFICIENCY } else { 1.0 };
+    return self.specs.max_thrust * throttle.clamp(-1.0, 1.0) * efficiency;
+  }
+
+  /// Calculate the forces from the propeller, pushing along the hull and walking the stern sideways
+  pub fn add_forces(&self, kinematics: Kinematics, throttle: f64, sink: &mut dyn ForceSink) {
+    let thrust = self.thrust(throttle);
+    if thrust == 0.0 {
+      return;
+    }
+    let prop_loc = kinematics.point(Vec2D::at_x(self.specs.prop_offset));
+    sink.add(ForceName::PropellerThrust, prop_loc, Vec2D::from_angle(kinematics.heading).scale(thrust));
+
+    // A right handed propeller walks the stern to port in reverse and to starboard going ahead
+    let walk = if thrust < 0.0 { self.specs.prop_walk } else { -self.specs.prop_walk * FORWARD_PROP_WALK };
+    let walk_vec = Vec2D::from_angle(kinematics.heading + PI * 0.5).scale(walk * thrust.abs());
+    sink.add(ForceName::PropWalk, prop_loc, walk_vec);
+  }
+
+  /// Burn the fuel used over one time step
+  pub fn burn(&mut self, throttle: f64) {
+    let used = throttle.clamp(-1.0, 1.0).abs() * self.specs.fuel_consumption * DELTA_TIME;
+    self.fuel = f64::max(self.fuel - used, 0.0);
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::{Engine, EngineSpecs, REVERSE_EFFICIENCY};
+  use crate::{geometry::Vec2D, physics::{Force, Kinematics}, simulation::DELTA_TIME};
+
+  /// A tank that lasts two seconds at full throttle
+  fn thirsty() -> Engine {
+    Engine::new(EngineSpecs::new(1.0, 0.1, -2.0, 1.0, 0.5))
+  }
+
+  #[test]
+  fn test_fuel_runs_out() {
+    let steps = (2.0 / DELTA_TIME).round() as usize;
+    let mut engine = thirsty();
+    for _ in 0..steps - 1 {
+      engine.burn(1.0);
+    }
+    assert!(engine.fuel > 0.0);
+    assert_eq!(engine.thrust(1.0), 1.0);
+    engine.burn(1.0);
+    engine.burn(1.0);
+    assert_eq!(engine.fuel, 0.0);
+
+    // With the tank empty the propeller stops turning
+    assert_eq!(engine.thrust(1.0), 0.0);
+    let mut forces: Vec<Force> = Vec::new();
+    engine.add_forces(Kinematics::new(Vec2D::zeros(), Vec2D::zeros(), 0.0, 0.0), 1.0, &mut forces);
+    assert!(forces.is_empty());
+  }
+
+  #[test]
+  fn test_fuel_use_follows_throttle() {
+    let mut engine = thirsty();
+    let mut idle = thirsty();
+    for _ in 0..(1.0 / DELTA_TIME).round() as usize {
+      engine.burn(-0.5);
+      idle.burn(0.0);
+    }
+    // A second at half throttle astern uses a quarter of the tank
+    assert!((engine.fuel - 0.75).abs() < 1e-9);
+    assert_eq!(idle.fuel, 1.0);
+    assert_eq!(engine.thrust(-0.5), -0.5 * REVERSE_EFFICIENCY);
+  }
+}
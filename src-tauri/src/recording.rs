@@ -0,0 +1,425 @@
+use std::{
+  collections::VecDeque,
+  fs,
+  sync::Mutex
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::{
+  events::{self, Publisher},
+  geometry::Vec2D,
+  physics::ForceName,
+  ship::{Ship, ShipState},
+  simulation::{SimSettings, Simulation},
+  towing::ShipLine
+};
+
+pub const DEFAULT_CAPACITY: usize = 18000; // Frames kept before the oldest are dropped, ten minutes at every step
+
+/// The state of every ship at one step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+  pub step: u64,
+  pub settings: SimSettings,
+  pub ships: Vec<ShipState>,
+  #[serde(default)]
+  pub lines: Vec<ShipLine>,
+  #[serde(default)]
+  pub marks: Vec<Vec2D>,
+  /// The magnitude of every force on each ship, with forces sharing a name added together, if forces were recorded
+  #[serde(default)]
+  pub forces: Vec<Vec<(ForceName, f64)>>,
+  /// Radians each ship leans to starboard
+  #[serde(default)]
+  pub heel: Vec<f64>,
+}
+impl Frame {
+  pub fn capture(sim: &Simulation, with_forces: bool) -> Self {
+    let forces = match with_forces {
+      true => sim.forces().iter().map(|forces| {
+        let mut magnitudes: Vec<(ForceName, f64)> = Vec::new();
+        for force in forces {
+          match magnitudes.iter_mut().find(|(name, _)| *name == force.name) {
+            Some((_, magnitude)) => *magnitude += force.vec.magnitude(),
+            None => magnitudes.push((force.name, force.vec.magnitude())),
+          }
+        }
+        magnitudes
+      }).collect(),
+      false => Vec::new(),
+    };
+    let settings = sim.get_settings();
+    let heel = sim.get_population().iter().map(|ship| ship.heel(settings.wind_angle, settings.wind_speed)).collect();
+    Self {
+      step: sim.get_step(),
+      settings,
+      ships: sim.ship_states(),
+      lines: sim.get_lines().clone(),
+      marks: sim.get_marks().clone(),
+      forces,
+      heel,
+    }
+  }
+
+  /// Put the simulation back as it was when the frame was captured
+  pub fn restore(&self, sim: &mut Simulation) -> Result<(), String> {
+    sim.restore_ship_states(self.step, &self.ships)?;
+    sim.restore_surroundings(self.settings, &self.lines, &self.marks);
+    return Result::Ok(());
+  }
+}
+
+/// Where playback of the recording is up to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Replay {
+  /// The step being shown, between frames while playing slower than recorded
+  cursor: f64,
+  playing: bool,
+  /// Recorded steps shown per real step, negative to play backwards
+  speed: f64,
+}
+
+/// What has been recorded, for display in the frontend
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RecordingInfo {
+  recording: bool,
+  interval: u64,
+  forces: bool,
+  frames: usize,
+  first_step: Option<u64>,
+  last_step: Option<u64>,
+  replay: Option<Replay>,
+}
+
+/// Keeps the recent history of the simulation so it can be replayed
+pub struct Recorder {
+  frames: VecDeque<Frame>,
+  capacity: usize,
+  interval: u64,
+  recording: bool,
+  /// Whether to work out the forces on every ship for each frame, which takes about as long as a step
+  forces: bool,
+  replay: Option<Replay>,
+  /// Every ship sailing when replay began, so ships launched part way through come back when playing past their launch
+  fleet: Vec<Box<dyn Ship>>,
+}
+impl Recorder {
+  pub fn new(capacity: usize, interval: u64) -> Self {
+    Self { frames: VecDeque::new(), capacity, interval, recording: true, forces: false, replay: None, fleet: Vec::new() }
+  }
+
+  pub fn get_frames(&self) -> &VecDeque<Frame> {
+    &self.frames
+  }
+
+  pub fn replaying(&self) -> bool {
+    self.replay.is_some()
+  }
+
+  /// Record the simulation if a frame is due, forgetting anything recorded after it
+  pub fn record(&mut self, sim: &Simulation) {
+    // After rewinding, what was recorded from here on is no longer what happened
+    while self.frames.back().map_or(false, |frame| frame.step >= sim.get_step()) {
+      self.frames.pop_back();
+    }
+    if !self.recording || sim.get_step() % self.interval != 0 {
+      return;
+    }
+    self.frames.push_back(Frame::capture(sim, self.forces));
+    while self.frames.len() > self.capacity {
+      self.frames.pop_front();
+    }
+  }
+
+  /// The last frame recorded at or before the given step
+  pub fn frame_at(&self, step: u64) -> Option<&Frame> {
+    let index = self.frames.partition_point(|frame| frame.step <= step);
+    match index {
+      0 => self.frames.front(),
+      _ => self.frames.get(index - 1),
+    }
+  }
+
+  /// Put the simulation back as it was at the frame, relaunching any ship dropped by going back before its launch
+  fn show(&self, frame: &Frame, sim: &mut Simulation) -> Result<(), String> {
+    for ship in self.fleet.iter().take(frame.ships.len()).skip(sim.get_population().len()) {
+      sim.add_ship(ship.clone());
+    }
+    return frame.restore(sim);
+  }
+
+  /// Enter replay, showing the simulation as it was at the given step
+  pub fn seek(&mut self, sim: &mut Simulation, step: u64) -> Result<u64, String> {
+    if self.replay.is_none() {
+      self.fleet = sim.get_population().clone();
+    }
+    let shown = match self.frame_at(step) {
+      Some(frame) => {
+        self.show(frame, sim)?;
+        frame.step
+      }
+      None => return Result::Err(String::from("Nothing has been recorded")),
+    };
+    let (playing, speed) = self.replay.map_or((false, 1.0), |replay| (replay.playing, replay.speed));
+    self.replay = Some(Replay { cursor: shown as f64, playing, speed });
+    return Result::Ok(shown);
+  }
+
+  /// Move playback on by one real step, returning whether the simulation was changed
+  pub fn advance(&mut self, sim: &mut Simulation) -> bool {
+    let mut replay = match self.replay {
+      Some(replay) if replay.playing => replay,
+      _ => return false,
+    };
+    let (first, last) = match (self.frames.front(), self.frames.back()) {
+      (Some(first), Some(last)) => (first.step as f64, last.step as f64),
+      _ => return false,
+    };
+    replay.cursor += replay.speed;
+    if replay.cursor <= first || replay.cursor >= last {
+      // Stop at whichever end of the recording was reached
+      replay.cursor = replay.cursor.clamp(first, last);
+      replay.playing = false;
+    }
+    self.replay = Some(replay);
+
+    let frame = self.frame_at(replay.cursor as u64).unwrap();
+    let changed = frame.step != sim.get_step();
+    if self.show(frame, sim).is_err() {
+      // The frame can't be shown any more, so leave the simulation where it is
+      self.stop_replay();
+      return false;
+    }
+    return changed;
+  }
+
+  /// Leave replay, so the simulation carries on from the step being shown
+  pub fn stop_replay(&mut self) {
+    self.replay = None;
+    self.fleet.clear();
+  }
+
+  pub fn clear(&mut self) {
+    self.frames.clear();
+    self.stop_replay();
+  }
+
+  pub fn info(&self) -> RecordingInfo {
+    RecordingInfo {
+      recording: self.recording,
+      interval: self.interval,
+      forces: self.forces,
+      frames: self.frames.len(),
+      first_step: self.frames.front().map(|frame| frame.step),
+      last_step: self.frames.back().map(|frame| frame.step),
+      replay: self.replay,
+    }
+  }
+}
+
+/// Play back one real step of the recording and send any updates that are due
+pub fn replay_and_publish(app: &AppHandle) {
+  let updates = {
+    let sim = app.state::<Mutex<Simulation>>();
+    let mut sim = sim.lock().unwrap();
+    let recorder = app.state::<Mutex<Recorder>>();
+    let mut recorder = recorder.lock().unwrap();
+    if !recorder.advance(&mut sim) {
+      return;
+    }
+    let publisher = app.state::<Mutex<Publisher>>();
+    let mut publisher = publisher.lock().unwrap();
+    match publisher.due() {
+      true => publisher.collect(&mut sim),
+      false => return,
+    }
+  };
+  events::emit(app, updates);
+}
+
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn set_recording(
+  recorder: State<Mutex<Recorder>>,
+  recording: bool,
+  interval: Option<u64>,
+  capacity: Option<usize>,
+  forces: Option<bool>
+) -> Result<(), String> {
+  if interval == Some(0) || capacity == Some(0) {
+    return Result::Err(String::from("Recording interval and capacity must be at least one"));
+  }
+  let mut recorder = recorder.lock().unwrap();
+  recorder.recording = recording;
+  recorder.interval = interval.unwrap_or(recorder.interval);
+  recorder.capacity = capacity.unwrap_or(recorder.capacity);
+  recorder.forces = forces.unwrap_or(recorder.forces);
+  while recorder.frames.len() > recorder.capacity {
+    recorder.frames.pop_front();
+  }
+  return Result::Ok(());
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_recording_info(recorder: State<Mutex<Recorder>>) -> RecordingInfo {
+  let recorder = recorder.lock().unwrap();
+  return recorder.info();
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn seek_replay(app: AppHandle, step: u64) -> Result<u64, String> {
+  let shown = {
+    let sim = app.state::<Mutex<Simulation>>();
+    let mut sim = sim.lock().unwrap();
+    let recorder = app.state::<Mutex<Recorder>>();
+    let mut recorder = recorder.lock().unwrap();
+    recorder.seek(&mut sim, step)?
+  };
+  events::publish(&app);
+  return Result::Ok(shown);
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn play_replay(app: AppHandle) -> Result<(), String> {
+  let sim = app.state::<Mutex<Simulation>>();
+  let mut sim = sim.lock().unwrap();
+  let recorder = app.state::<Mutex<Recorder>>();
+  let mut recorder = recorder.lock().unwrap();
+  if recorder.replay.is_none() {
+    let step = sim.get_step();
+    recorder.seek(&mut sim, step)?;
+  }
+  if let Some(replay) = recorder.replay.as_mut() {
+    replay.playing = true;
+  }
+  return Result::Ok(());
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn pause_replay(recorder: State<Mutex<Recorder>>) {
+  let mut recorder = recorder.lock().unwrap();
+  if let Some(replay) = recorder.replay.as_mut() {
+    replay.playing = false;
+  }
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn set_replay_speed(recorder: State<Mutex<Recorder>>, speed: f64) -> Result<(), String> {
+  if !(speed != 0.0 && speed.is_finite()) {
+    return Result::Err(String::from("Replay speed must be a non-zero number"));
+  }
+  let mut recorder = recorder.lock().unwrap();
+  match recorder.replay.as_mut() {
+    Some(replay) => {
+      replay.speed = speed;
+      return Result::Ok(());
+    }
+    None => return Result::Err(String::from("Not replaying")),
+  }
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn stop_replay(recorder: State<Mutex<Recorder>>) {
+  let mut recorder = recorder.lock().unwrap();
+  recorder.stop_replay();
+}
+
+/// Write every recorded frame to a JSON file
+#[tauri::command(rename_all = "snake_case")]
+pub fn save_recording(recorder: State<Mutex<Recorder>>, path: String) -> Result<(), String> {
+  let recorder = recorder.lock().unwrap();
+  let json = serde_json::to_string(&recorder.frames).map_err(|e| e.to_string())?;
+  return fs::write(path, json).map_err(|e| e.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Recorder;
+  use crate::{
+    geometry::Vec2D,
+    scenario::{Scenario, ShipPreset, WindShift},
+    simulation::{SimSettings, Simulation}
+  };
+
+  #[test]
+  fn test_seek_across_wind_shift() {
+    let mut scenario = Scenario::bundled("sandbox").unwrap();
+    scenario.wind.shifts = vec![WindShift { time: 2.0, angle: 1.0, speed: 8.0 }];
+    scenario.script = Some(String::from(r#"
+      fn on_step(world) {
+        if world.step == 90 {
+          place_mark(10.0, 20.0);
+        }
+      }
+    "#));
+    let mut sim = Simulation::from_scenario(scenario).unwrap();
+    let before = sim.get_settings();
+    let mut recorder = Recorder::new(1000, 1);
+    recorder.record(&sim);
+    for step in 0..120 {
+      if step == 45 {
+        sim.raft_ships(1, 2).unwrap();
+      }
+      sim.step();
+      recorder.record(&sim);
+    }
+    let after = sim.get_settings();
+    assert_eq!(after, SimSettings::new(1.0, 8.0).with_current(before.current));
+
+    // Back before the shift, the lines and the mark
+    assert_eq!(recorder.seek(&mut sim, 30), Ok(30));
+    assert_eq!(sim.get_settings(), before);
+    assert!(sim.get_lines().is_empty());
+    assert!(sim.get_marks().is_empty());
+
+    // Playing forward brings them back as they happened
+    recorder.replay.as_mut().unwrap().playing = true;
+    for _ in 0..70 {
+      recorder.advance(&mut sim);
+    }
+    assert_eq!(sim.get_step(), 100);
+    assert_eq!(sim.get_settings(), after);
+    assert_eq!(sim.get_lines().len(), 2);
+    assert_eq!(sim.get_marks().len(), 1);
+  }
+
+  #[test]
+  fn test_seek_restores_tethers_fuel_and_population() {
+    let mut sim = Simulation::new(0, 0.0, 5.0);
+    let mut recorder = Recorder::new(1000, 1);
+    sim.update_ship_throttle(4, 1.0);
+    recorder.record(&sim);
+    for step in 0..60 {
+      match step {
+        20 => sim.drop_anchor(4, 10.0).unwrap(),
+        40 => { sim.spawn_ship(ShipPreset::Dinghy, Vec2D::new(0.0, 0.0), 0.0); }
+        _ => {}
+      }
+      sim.step();
+      recorder.record(&sim);
+    }
+    let fuel = |sim: &Simulation| sim.get_ship(4).unwrap().engine().unwrap().fuel;
+    let burnt = fuel(&sim);
+    assert_eq!(sim.get_population().len(), 6);
+
+    // Before the anchor was dropped and the dinghy launched, with more fuel in the tank
+    assert_eq!(recorder.seek(&mut sim, 10), Ok(10));
+    assert!(sim.get_ship(4).unwrap().tethers().is_empty());
+    assert!(fuel(&sim) > burnt);
+    assert_eq!(sim.get_population().len(), 5);
+
+    // Forward again the anchor is back down and the dinghy relaunched
+    assert_eq!(recorder.seek(&mut sim, 30), Ok(30));
+    assert_eq!(sim.get_ship(4).unwrap().tethers().len(), 1);
+    assert_eq!(sim.get_population().len(), 5);
+    assert_eq!(recorder.seek(&mut sim, 50), Ok(50));
+    assert_eq!(sim.get_population().len(), 6);
+
+    // Frames with ships the simulation never had can't be shown
+    let states = sim.ship_states();
+    sim.reset();
+    assert!(sim.restore_ship_states(50, &states).is_err());
+  }
+}
@@ -0,0 +1,305 @@
+use std::{
+  io::{self, BufRead, BufReader, ErrorKind, Write},
+  net::{TcpListener, TcpStream},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex
+  },
+  thread,
+  time::Duration
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::{
+  events,
+  geometry::Vec2D,
+  recording::Recorder,
+  runner::{self, Runner},
+  ship::{Ship, ShipControls},
+  simulation::Simulation,
+  snapshots::Snapshots
+};
+
+pub const DEFAULT_REMOTE_PORT: u16 = 7411;
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// An operation asked for by a client, sent as one JSON object per line
+///
+/// Each is answered by one line of `{"Ok": <result>}` or `{"Err": "<message>"}`, in the order they were sent.
+/// The README lists every request with an example.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum Request {
+  /// Step once returning the step reached, or step the given number of times returning where the ships ended up
+  Step { steps: Option<u64> },
+  Start,
+  Pause,
+  GetRunState,
+  GetSettings,
+  /// Change the wind, and the current if one is given
+  SetSettings { wind_angle: f64, wind_speed: f64, #[serde(default)] current: Option<Vec2D> },
+  /// The shape of every ship
+  GetPopulation,
+  /// Everything about one ship
+  GetShip { index: usize },
+  GetControls { index: usize },
+  SetControls { index: usize, controls: ShipControls },
+  SetThrottle { index: usize, throttle: f64 },
+}
+
+/// Whether the server is running, and the port it listens on
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RemoteInfo {
+  pub port: Option<u16>,
+}
+
+/// The control server, which lets other programs on this computer drive the simulation
+pub struct RemoteServer {
+  port: Option<u16>,
+  stop: Arc<AtomicBool>,
+}
+impl RemoteServer {
+  pub fn new() -> Self {
+    Self { port: None, stop: Arc::new(AtomicBool::new(false)) }
+  }
+
+  pub fn info(&self) -> RemoteInfo {
+    RemoteInfo { port: self.port }
+  }
+}
+impl Default for RemoteServer {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Start listening on localhost, stopping any server already running on another port
+///
+/// Connected clients are kept until they disconnect, even after the server stops accepting new ones.
+pub fn start(app: &AppHandle, port: u16) -> io::Result<()> {
+  if app.state::<Mutex<RemoteServer>>().lock().unwrap().port == Some(port) {
+    return Ok(());
+  }
+  let listener = TcpListener::bind(("127.0.0.1", port))?;
+  listener.set_nonblocking(true)?;
+  let stop = Arc::new(AtomicBool::new(false));
+  {
+    let server = app.state::<Mutex<RemoteServer>>();
+    let mut server = server.lock().unwrap();
+    server.stop.store(true, Ordering::Relaxed);
+    server.stop = stop.clone();
+    server.port = Some(port);
+  }
+
+  let app = app.clone();
+  thread::spawn(move || {
+    while !stop.load(Ordering::Relaxed) {
+      match listener.accept() {
+        Ok((stream, _)) => {
+          let app = app.clone();
+          // Mistakes in requests are answered, so an error here only means the client has gone
+          thread::spawn(move || handle_client(&app, stream));
+        }
+        Err(error) if error.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL_INTERVAL),
+        Err(_) => {
+          // Show the server as stopped, unless another has already replaced it
+          let server = app.state::<Mutex<RemoteServer>>();
+          let mut server = server.lock().unwrap();
+          if Arc::ptr_eq(&server.stop, &stop) {
+            server.port = None;
+          }
+          break;
+        }
+      }
+    }
+  });
+  return Ok(());
+}
+
+fn handle_client(app: &AppHandle, stream: TcpStream) -> io::Result<()> {
+  // Accepted sockets can inherit non-blocking mode from the listener
+  stream.set_nonblocking(false)?;
+  return answer(stream, |request| handle_request(app, request));
+}
+
+/// Answer every request line from the client with a line of the result, until it disconnects
+fn answer(stream: TcpStream, mut handle: impl FnMut(Request) -> Result<serde_json::Value, String>) -> io::Result<()> {
+  let mut writer = stream.try_clone()?;
+  for line in BufReader::new(stream).lines() {
+    let line = line?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    let response = match serde_json::from_str::<Request>(&line) {
+      Ok(request) => handle(request),
+      Err(error) => Err(error.to_string()),
+    };
+    writeln!(writer, "{}", serde_json::to_string(&response).unwrap())?;
+  }
+  return Ok(());
+}
+
+/// Carry out a request the same way as the matching command, and send the frontend whatever it changed
+pub fn handle_request(app: &AppHandle, request: Request) -> Result<serde_json::Value, String> {
+  let result = match request {
+    Request::Start => {
+      runner::start_simulation(app.state::<Mutex<Runner>>());
+      Ok(serde_json::Value::Null)
+    }
+    Request::Pause => {
+      runner::pause_simulation(app.state::<Mutex<Runner>>());
+      Ok(serde_json::Value::Null)
+    }
+    Request::GetRunState => {
+      serde_json::to_value(runner::get_run_state(app.state::<Mutex<Runner>>(), app.state::<Mutex<Simulation>>()))
+        .map_err(|e| e.to_string())
+    }
+    request => {
+      let sim = app.state::<Mutex<Simulation>>();
+      let mut sim = sim.lock().unwrap();
+      let recorder = app.state::<Mutex<Recorder>>();
+      let mut recorder = recorder.lock().unwrap();
+      let snapshots = app.state::<Mutex<Snapshots>>();
+      let mut snapshots = snapshots.lock().unwrap();
+      if let Request::Step { .. } = request {
+        // Stepping carries on from whatever was being replayed
+        recorder.stop_replay();
+      }
+      apply(&mut sim, request, |sim| {
+        recorder.record(sim);
+        snapshots.auto_snapshot(sim);
+      })
+    }
+  };
+  events::publish(app);
+  return result;
+}
+
+/// Carry out a request on the simulation, calling `after_step` after every step it takes
+pub fn apply(sim: &mut Simulation, request: Request, mut after_step: impl FnMut(&Simulation)) -> Result<serde_json::Value, String> {
+  let result = match request {
+    Request::Step { steps: None } => {
+      sim.step();
+      after_step(sim);
+      serde_json::to_value(sim.get_step())
+    }
+    Request::Step { steps: Some(steps) } => serde_json::to_value(sim.run_batch(steps, None, after_step)),
+    Request::Start | Request::Pause | Request::GetRunState => {
+      return Result::Err(String::from("Only the app can start and pause the simulation"));
+    }
+    Request::GetSettings => serde_json::to_value(sim.get_settings()),
+    Request::SetSettings { wind_angle, wind_speed, current } => {
+      sim.set_settings(wind_angle, wind_speed);
+      if let Some(current) = current {
+        sim.set_current(current);
+      }
+      Ok(serde_json::Value::Null)
+    }
+    Request::GetPopulation => serde_json::to_value(sim.shapes()),
+    Request::GetShip { index } => Ok(ship(sim, index)?.to_json()),
+    Request::GetControls { index } => serde_json::to_value(ship(sim, index)?.controls()),
+    Request::SetControls { index, controls } => {
+      ship(sim, index)?;
+      sim.update_ship_controls(index, controls);
+      Ok(serde_json::Value::Null)
+    }
+    Request::SetThrottle { index, throttle } => {
+      ship(sim, index)?;
+      sim.update_ship_throttle(index, throttle);
+      Ok(serde_json::Value::Null)
+    }
+  };
+  return result.map_err(|e| e.to_string());
+}
+
+fn ship(sim: &Simulation, index: usize) -> Result<&dyn Ship, String> {
+  return sim.get_ship(index).ok_or(String::from("No such ship"));
+}
+
+
+/// Start the control server, on the default port if none is given
+#[tauri::command(rename_all = "snake_case")]
+pub fn start_remote_server(app: AppHandle, port: Option<u16>) -> Result<RemoteInfo, String> {
+  start(&app, port.unwrap_or(DEFAULT_REMOTE_PORT)).map_err(|e| e.to_string())?;
+  let server = app.state::<Mutex<RemoteServer>>();
+  let server = server.lock().unwrap();
+  return Result::Ok(server.info());
+}
+
+#[tauri::command]
+pub fn stop_remote_server(server: State<Mutex<RemoteServer>>) {
+  let mut server = server.lock().unwrap();
+  server.stop.store(true, Ordering::Relaxed);
+  server.port = None;
+}
+
+#[tauri::command]
+pub fn get_remote_server(server: State<Mutex<RemoteServer>>) -> RemoteInfo {
+  let server = server.lock().unwrap();
+  return server.info();
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    thread
+  };
+  use super::{answer, apply, Request};
+  use crate::{ship::ShipControls, simulation::Simulation};
+
+  #[test]
+  fn test_requests_reach_the_simulation() {
+    let mut sim = Simulation::new(12345, 0.0, 5.0);
+    let mut steps_taken = 0;
+    assert_eq!(apply(&mut sim, Request::Step { steps: None }, |_| steps_taken += 1), Ok(serde_json::json!(1)));
+    let batch = apply(&mut sim, Request::Step { steps: Some(29) }, |_| steps_taken += 1).unwrap();
+    assert_eq!(batch["step"], 30);
+    assert_eq!(batch["ships"].as_array().unwrap().len(), sim.get_population().len());
+    assert_eq!(steps_taken, 30);
+
+    let controls = ShipControls::new(vec![3.0], 0.2);
+    assert_eq!(apply(&mut sim, Request::SetControls { index: 1, controls: controls.clone() }, |_| {}), Ok(serde_json::Value::Null));
+    assert_eq!(sim.get_ship(1).unwrap().controls(), &controls);
+    let read = apply(&mut sim, Request::GetControls { index: 1 }, |_| {}).unwrap();
+    assert_eq!(serde_json::from_value::<ShipControls>(read).unwrap(), controls);
+
+    let ships = sim.get_population().len();
+    assert_eq!(apply(&mut sim, Request::GetShip { index: ships }, |_| {}), Err(String::from("No such ship")));
+    assert_eq!(apply(&mut sim, Request::SetThrottle { index: ships, throttle: 1.0 }, |_| {}), Err(String::from("No such ship")));
+    // Nothing is logged for a ship that isn't there
+    assert!(sim.get_log().inputs.iter().all(|entry| entry.step == 30));
+    assert_eq!(sim.get_log().inputs.len(), 1);
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+      let (stream, _) = listener.accept().unwrap();
+      let sim = Simulation::new(12345, 0.5, 3.0);
+      answer(stream, |request| match request {
+        Request::GetSettings => serde_json::to_value(sim.get_settings()).map_err(|e| e.to_string()),
+        _ => Err(String::from("Not expected")),
+      }).unwrap();
+    });
+
+    let mut client = TcpStream::connect(address).unwrap();
+    client.write_all(b"\"GetSettings\"\n\n{\"Steer\": 1}\n").unwrap();
+    let mut lines = BufReader::new(client.try_clone().unwrap()).lines();
+    let settings: serde_json::Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+    assert_eq!(settings["Ok"]["wind_angle"], 0.5);
+    assert_eq!(settings["Ok"]["wind_speed"], 3.0);
+    // Blank lines are skipped, and a request that can't be read is answered with why
+    let error: serde_json::Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+    assert!(error["Err"].as_str().unwrap().contains("Steer"));
+
+    drop(lines);
+    drop(client);
+    server.join().unwrap();
+  }
+}
@@ -0,0 +1,171 @@
+use std::{
+  sync::Mutex,
+  thread,
+  time::{Duration, Instant}
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::{
+  events,
+  recording::{self, Recorder},
+  simulation::{Simulation, DELTA_TIME}
+};
+
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_millis(20);
+const MAX_LAG: Duration = Duration::from_millis(250); // Give up catching up if stepping falls this far behind
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RunSpeed {
+  /// Simulated time passes this many times faster than real time, 1.0 being real time
+  Warp(f64),
+  /// Step as fast as possible
+  Unlimited,
+}
+impl RunSpeed {
+  pub fn validate(&self) -> Result<(), String> {
+    if let RunSpeed::Warp(warp) = self {
+      if !(*warp > 0.0 && warp.is_finite()) {
+        return Result::Err(String::from("Time warp must be greater than zero"));
+      }
+    }
+    return Result::Ok(());
+  }
+}
+
+/// How the background loop is currently stepping the simulation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RunState {
+  pub running: bool,
+  pub speed: RunSpeed,
+  pub step: u64,
+}
+
+pub struct Runner {
+  running: bool,
+  speed: RunSpeed,
+}
+impl Runner {
+  pub fn new() -> Self {
+    Self { running: false, speed: RunSpeed::Warp(1.0) }
+  }
+
+  /// Real time to wait between steps, or None to not wait at all
+  fn step_interval(&self) -> Option<Duration> {
+    match self.speed {
+      RunSpeed::Warp(warp) => Some(Duration::from_secs_f64(DELTA_TIME / warp)),
+      RunSpeed::Unlimited => None,
+    }
+  }
+}
+impl Default for Runner {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Start the thread that steps the simulation in the background whenever the runner is running
+pub fn spawn(app: AppHandle) {
+  thread::spawn(move || {
+    let mut next_step = Instant::now();
+    loop {
+      let (running, interval) = {
+        let runner = app.state::<Mutex<Runner>>();
+        let runner = runner.lock().unwrap();
+        (runner.running, runner.step_interval())
+      };
+
+      if !running {
+        let replaying = app.state::<Mutex<Recorder>>().lock().unwrap().replaying();
+        match replaying {
+          true => {
+            recording::replay_and_publish(&app);
+            thread::sleep(Duration::from_secs_f64(DELTA_TIME));
+          }
+          false => thread::sleep(PAUSED_POLL_INTERVAL),
+        }
+        next_step = Instant::now();
+        continue;
+      }
+
+      events::step_and_publish(&app, false);
+      if app.state::<Mutex<Simulation>>().lock().unwrap().finished() {
+        app.state::<Mutex<Runner>>().lock().unwrap().running = false;
+      }
+
+      match interval {
+        Some(interval) => {
+          next_step += interval;
+          let now = Instant::now();
+          if next_step > now {
+            thread::sleep(next_step - now);
+          } else if now - next_step > MAX_LAG {
+            // Stepping can't keep up, so run behind rather than trying to catch up in a burst
+            next_step = now;
+          }
+        }
+        None => {
+          // Let commands get a turn at the simulation lock
+          thread::yield_now();
+        }
+      }
+    }
+  });
+}
+
+
+#[tauri::command]
+pub fn start_simulation(runner: State<Mutex<Runner>>) {
+  let mut runner = runner.lock().unwrap();
+  runner.running = true;
+}
+
+#[tauri::command]
+pub fn pause_simulation(runner: State<Mutex<Runner>>) {
+  let mut runner = runner.lock().unwrap();
+  runner.running = false;
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn set_simulation_speed(runner: State<Mutex<Runner>>, speed: RunSpeed) -> Result<(), String> {
+  speed.validate()?;
+  let mut runner = runner.lock().unwrap();
+  runner.speed = speed;
+  return Result::Ok(());
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_run_state(runner: State<Mutex<Runner>>, sim: State<Mutex<Simulation>>) -> RunState {
+  let runner = runner.lock().unwrap();
+  let sim = sim.lock().unwrap();
+  return RunState { running: runner.running, speed: runner.speed, step: sim.get_step() };
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+  use super::{RunSpeed, Runner};
+  use crate::simulation::DELTA_TIME;
+
+  #[test]
+  fn test_step_interval_follows_speed() {
+    let mut runner = Runner::new();
+    assert!(!runner.running);
+    assert_eq!(runner.step_interval(), Some(Duration::from_secs_f64(DELTA_TIME)));
+    runner.speed = RunSpeed::Warp(4.0);
+    assert_eq!(runner.step_interval(), Some(Duration::from_secs_f64(DELTA_TIME / 4.0)));
+    runner.speed = RunSpeed::Unlimited;
+    assert_eq!(runner.step_interval(), None);
+  }
+
+  #[test]
+  fn test_speed_is_validated() {
+    assert!(RunSpeed::Warp(0.5).validate().is_ok());
+    assert!(RunSpeed::Unlimited.validate().is_ok());
+    for warp in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+      assert!(RunSpeed::Warp(warp).validate().is_err(), "{}", warp);
+    }
+  }
+}
@@ -0,0 +1,408 @@
+use std::{fs, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::{
+  catamaran::{Catamaran, CatamaranSpecs},
+  geometry::Vec2D,
+  propulsion::{Engine, EngineSpecs},
+  recording::Recorder,
+  scripting::check_controller,
+  ship::{AdjustableShip, Ship, ShipControls, ShipSpecs},
+  simulation::{SimSettings, Simulation, DELTA_TIME},
+  snapshots::Snapshots
+};
+
+/// Scenarios built into the app, by id
+const BUNDLED: [(&str, &str); 5] = [
+  ("sandbox", include_str!("../scenarios/sandbox.json")),
+  ("downwind_run", include_str!("../scenarios/downwind_run.json")),
+  ("wind_shift", include_str!("../scenarios/wind_shift.json")),
+  ("crossing_the_tide", include_str!("../scenarios/crossing_the_tide.json")),
+  ("autopilot", include_str!("../scenarios/autopilot.json")),
+];
+
+/// The kinds of ship a scenario can start with
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ShipPreset {
+  Dinghy,
+  Catamaran,
+  MotorBoat,
+}
+impl ShipPreset {
+  /// The preset with the name scripts use for it
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "dinghy" => Some(ShipPreset::Dinghy),
+      "catamaran" => Some(ShipPreset::Catamaran),
+      "motor_boat" => Some(ShipPreset::MotorBoat),
+      _ => None,
+    }
+  }
+
+  /// A new ship of this kind, drifting at the given velocity, which is the current's for a ship at rest in the water
+  pub fn build(&self, loc: Vec2D, vel: Vec2D, heading: f64) -> Box<dyn Ship> {
+    match self {
+      ShipPreset::Dinghy => Box::new(AdjustableShip::new(
+        ShipSpecs::default(), loc, vel, 0.0, heading, ShipControls::new(vec![0.0], 0.0)
+      )),
+      ShipPreset::Catamaran => Box::new(Catamaran::new(
+        CatamaranSpecs::default(), loc, vel, 0.0, heading, ShipControls::new(vec![0.0], 0.0)
+      ).with_engine(Engine::new(EngineSpecs::auxiliary(-4.5)))),
+      ShipPreset::MotorBoat => Box::new(AdjustableShip::new(
+        ShipSpecs::motor_boat(), loc, vel, 0.0, heading, ShipControls::new(vec![], 0.0)
+      ).with_engine(Engine::new(EngineSpecs::inboard(-3.5)))),
+    }
+  }
+}
+
+/// A ship as it is when the scenario starts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShipDefinition {
+  pub preset: ShipPreset,
+  pub loc: Vec2D,
+  pub heading: f64,
+  /// Source of a controller script to steer the ship, as written in the script tab
+  #[serde(default)]
+  pub controller: Option<String>,
+}
+
+/// A change in the wind, some seconds after the scenario starts
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindShift {
+  pub time: f64,
+  pub angle: f64,
+  pub speed: f64,
+}
+
+/// The wind the scenario starts with, and how it changes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindModel {
+  /// The direction the wind comes from
+  pub angle: f64,
+  pub speed: f64,
+  #[serde(default)]
+  pub shifts: Vec<WindShift>,
+}
+
+/// A change in the current, some seconds after the scenario starts
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CurrentShift {
+  pub time: f64,
+  pub velocity: Vec2D,
+}
+
+/// The current the scenario starts with, and how it changes, like a turning tide
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurrentModel {
+  /// Velocity of the water over the ground, the same everywhere
+  pub velocity: Vec2D,
+  #[serde(default)]
+  pub shifts: Vec<CurrentShift>,
+}
+
+/// The area the scenario is sailed in, centred on the origin
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScenarioMap {
+  pub width: f64,
+  pub height: f64,
+}
+impl ScenarioMap {
+  /// Whether the point is on the map, edges included
+  pub fn contains(&self, point: Vec2D) -> bool {
+    point.x.abs() <= self.width * 0.5 && point.y.abs() <= self.height * 0.5
+  }
+}
+impl Default for ScenarioMap {
+  fn default() -> Self {
+    Self { width: 10000.0, height: 10000.0 }
+  }
+}
+
+/// Everything needed to start a run, as saved in a scenario file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scenario {
+  pub name: String,
+  #[serde(default)]
+  pub description: String,
+  pub seed: u64,
+  pub wind: WindModel,
+  #[serde(default)]
+  pub current: Option<CurrentModel>,
+  #[serde(default)]
+  pub map: ScenarioMap,
+  pub ships: Vec<ShipDefinition>,
+  /// Marks to sail round, in order
+  #[serde(default)]
+  pub course: Vec<Vec2D>,
+  /// Seconds until the scenario is over and the simulation stops running, or forever if not given
+  #[serde(default)]
+  pub duration: Option<f64>,
+  /// Source of a scenario script to run when the scenario starts, as written in the script tab
+  #[serde(default)]
+  pub script: Option<String>,
+}
+impl Scenario {
+  /// Read a scenario from JSON, checking that it makes sense
+  pub fn from_json(json: &str) -> Result<Self, String> {
+    let scenario: Scenario = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    scenario.validate()?;
+    return Result::Ok(scenario);
+  }
+
+  /// One of the scenarios built into the app
+  pub fn bundled(id: &str) -> Result<Self, String> {
+    match BUNDLED.iter().find(|(bundled_id, _)| *bundled_id == id) {
+      Some((_, json)) => Self::from_json(json),
+      None => Result::Err(format!("No scenario called {}", id)),
+    }
+  }
+
+  /// Ships to experiment with, in the given wind
+  pub fn sandbox(seed: u64, wind_angle: f64, wind_speed: f64) -> Self {
+    let mut scenario = Self::bundled("sandbox").unwrap();
+    scenario.seed = seed;
+    scenario.wind = WindModel { angle: wind_angle, speed: wind_speed, shifts: Vec::new() };
+    return scenario;
+  }
+
+  pub fn validate(&self) -> Result<(), String> {
+    let times = self.wind.shifts.iter().map(|shift| shift.time)
+      .chain(self.current.iter().flat_map(|current| current.shifts.iter().map(|shift| shift.time)));
+    for time in times {
+      if !(time >= 0.0 && time.is_finite()) {
+        return Result::Err(String::from("Changes in the wind and current must be at zero or more seconds"));
+      }
+    }
+    let speeds = std::iter::once(self.wind.speed).chain(self.wind.shifts.iter().map(|shift| shift.speed));
+    for speed in speeds {
+      if !(speed >= 0.0 && speed.is_finite()) {
+        return Result::Err(String::from("Wind speeds must be zero or more"));
+      }
+    }
+    let currents = self.current.iter()
+      .flat_map(|current| std::iter::once(current.velocity).chain(current.shifts.iter().map(|shift| shift.velocity)));
+    for current in currents {
+      if !(current.x.is_finite() && current.y.is_finite()) {
+        return Result::Err(String::from("Currents must have a speed and direction"));
+      }
+    }
+    if !(self.map.width > 0.0 && self.map.height > 0.0 && self.map.width.is_finite() && self.map.height.is_finite()) {
+      return Result::Err(String::from("The map must have some area"));
+    }
+    for (index, ship) in self.ships.iter().enumerate() {
+      // The map is finite, so this also rules out locations that aren't numbers
+      if !self.map.contains(ship.loc) {
+        return Result::Err(format!("Ship {} must start on the map", index));
+      }
+      if !ship.heading.is_finite() {
+        return Result::Err(format!("Ship {} must have a heading", index));
+      }
+      if let Some(source) = &ship.controller {
+        check_controller(source).map_err(|e| format!("Controller for ship {}: {}", index, e))?;
+      }
+    }
+    if !self.course.iter().all(|mark| self.map.contains(*mark)) {
+      return Result::Err(String::from("Every mark of the course must be on the map"));
+    }
+    if let Some(duration) = self.duration {
+      if !(duration > 0.0 && duration.is_finite()) {
+        return Result::Err(String::from("Duration must be greater than zero"));
+      }
+    }
+    return Result::Ok(());
+  }
+
+  /// The wind and current when the scenario starts
+  pub fn start_settings(&self) -> SimSettings {
+    let current = self.current.as_ref().map_or(Vec2D::zeros(), |current| current.velocity);
+    return SimSettings::new(self.wind.angle, self.wind.speed).with_current(current);
+  }
+
+  /// The wind shift due before the given step is taken, if there is one
+  pub fn wind_shift_at(&self, step: u64) -> Option<WindShift> {
+    self.wind.shifts.iter().rev().find(|shift| to_step(shift.time) == step).copied()
+  }
+
+  /// The change in the current due before the given step is taken, if there is one
+  pub fn current_shift_at(&self, step: u64) -> Option<CurrentShift> {
+    let current = self.current.as_ref()?;
+    current.shifts.iter().rev().find(|shift| to_step(shift.time) == step).copied()
+  }
+
+  /// The step after which the scenario is over
+  pub fn end_step(&self) -> Option<u64> {
+    self.duration.map(to_step)
+  }
+
+  /// The scenario without anything that acts on it while it runs, for replaying a log where everything they did was recorded
+  pub fn starting_state(&self) -> Self {
+    let mut scenario = self.clone();
+    scenario.wind.shifts.clear();
+    if let Some(current) = &mut scenario.current {
+      current.shifts.clear();
+    }
+    scenario.ships.iter_mut().for_each(|ship| ship.controller = None);
+    scenario.script = None;
+    return scenario;
+  }
+}
+
+fn to_step(time: f64) -> u64 {
+  (time / DELTA_TIME).round() as u64
+}
+
+/// A bundled scenario, as listed for choosing one
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScenarioSummary {
+  pub id: String,
+  pub name: String,
+  pub description: String,
+}
+
+/// Start a scenario, forgetting the recording and snapshots of the run before
+fn start(sim: &Mutex<Simulation>, recorder: &Mutex<Recorder>, snapshots: &Mutex<Snapshots>, scenario: Scenario) -> Result<(), String> {
+  let mut sim = sim.lock().unwrap();
+  recorder.lock().unwrap().clear();
+  snapshots.lock().unwrap().clear();
+  return sim.load_scenario(scenario);
+}
+
+
+/// Start the scenario in a JSON file
+#[tauri::command(rename_all = "snake_case")]
+pub fn load_scenario(
+  sim: State<Mutex<Simulation>>,
+  recorder: State<Mutex<Recorder>>,
+  snapshots: State<Mutex<Snapshots>>,
+  path: String
+) -> Result<(), String> {
+  let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+  let scenario = Scenario::from_json(&json)?;
+  return start(sim.inner(), recorder.inner(), snapshots.inner(), scenario);
+}
+
+/// Start one of the scenarios built into the app
+#[tauri::command(rename_all = "snake_case")]
+pub fn load_bundled_scenario(
+  sim: State<Mutex<Simulation>>,
+  recorder: State<Mutex<Recorder>>,
+  snapshots: State<Mutex<Snapshots>>,
+  id: String
+) -> Result<(), String> {
+  let scenario = Scenario::bundled(&id)?;
+  return start(sim.inner(), recorder.inner(), snapshots.inner(), scenario);
+}
+
+#[tauri::command]
+pub fn list_bundled_scenarios() -> Vec<ScenarioSummary> {
+  return BUNDLED.iter().map(|(id, json)| {
+    let scenario = Scenario::from_json(json).unwrap();
+    ScenarioSummary { id: id.to_string(), name: scenario.name, description: scenario.description }
+  }).collect();
+}
+
+/// The scenario the simulation resets to
+#[tauri::command]
+pub fn get_scenario(sim: State<Mutex<Simulation>>) -> Scenario {
+  let sim = sim.lock().unwrap();
+  return sim.get_scenario().clone();
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::f64::consts::PI;
+  use super::{CurrentModel, CurrentShift, Scenario, ShipDefinition, ShipPreset, BUNDLED};
+  use crate::{determinism::verify, events::SimEvent, geometry::Vec2D, simulation::Simulation};
+
+  #[test]
+  fn test_bundled_scenarios_reproduce() {
+    for (id, _) in BUNDLED {
+      // Through every change of wind and current, to the end
+      let scenario = Scenario::bundled(id).unwrap();
+      let steps = scenario.end_step().unwrap_or(300);
+      let mut sim = Simulation::from_scenario(scenario).unwrap();
+      sim.run(steps, None, |_| {});
+      assert_eq!(sim.get_step(), steps);
+      let report = verify(sim.get_log()).unwrap();
+      assert_eq!(report.mismatch, None, "{} did not reproduce", id);
+      assert_eq!(report.steps, steps, "{} was not verified to the end", id);
+    }
+  }
+
+  #[test]
+  fn test_scripted_spawn_reproduces() {
+    let mut scenario = Scenario::bundled("sandbox").unwrap();
+    let ships = scenario.ships.len();
+    scenario.script = Some(String::from(r#"
+      fn on_step(world) {
+        if world.step == 60 {
+          let boat = add_ship("motor_boat", 0.0, -30.0, 0.5);
+          set_throttle(boat, 1.0);
+        }
+      }
+    "#));
+    let mut sim = Simulation::from_scenario(scenario).unwrap();
+    sim.run(300, None, |_| {});
+    assert_eq!(sim.get_population().len(), ships + 1);
+    let report = verify(sim.get_log()).unwrap();
+    assert_eq!(report.mismatch, None);
+    assert_eq!(report.steps, 300);
+  }
+
+  #[test]
+  fn test_collisions_and_marks_reported() {
+    // Two motor boats motoring head on through a mark between them
+    let mut scenario = Scenario::sandbox(1, 0.0, 0.0);
+    scenario.ships = [(-6.0, 0.0), (6.0, PI)].iter().map(|(x, heading)| ShipDefinition {
+      preset: ShipPreset::MotorBoat, loc: Vec2D::new(*x, 0.0), heading: *heading, controller: None,
+    }).collect();
+    scenario.course = vec![Vec2D::zeros()];
+    let mut sim = Simulation::from_scenario(scenario).unwrap();
+    sim.update_ship_throttle(0, 1.0);
+    sim.update_ship_throttle(1, 1.0);
+    sim.run(600, None, |_| {});
+    let events = sim.drain_events();
+    let collisions = events.iter().filter(|event| **event == SimEvent::Collision { a: 0, b: 1 }).count();
+    assert_eq!(collisions, 1);
+    for ship in [0, 1] {
+      assert!(events.contains(&SimEvent::MarkReached { ship, mark: 0 }));
+      assert!(events.contains(&SimEvent::CourseFinished { ship }));
+    }
+  }
+
+  #[test]
+  fn test_validate() {
+    let valid = Scenario::sandbox(1, 0.0, 5.0);
+    assert!(valid.validate().is_ok());
+
+    let mut scenario = valid.clone();
+    scenario.ships[0].loc = Vec2D::new(f64::NAN, 0.0);
+    assert!(scenario.validate().is_err());
+
+    let mut scenario = valid.clone();
+    scenario.ships[0].loc = Vec2D::new(scenario.map.width, 0.0);
+    assert!(scenario.validate().is_err());
+
+    let mut scenario = valid.clone();
+    scenario.ships[0].heading = f64::INFINITY;
+    assert!(scenario.validate().is_err());
+
+    let mut scenario = valid.clone();
+    scenario.ships[0].controller = Some(String::from("fn control(ship, world) {"));
+    assert!(scenario.validate().unwrap_err().starts_with("Controller for ship 0"));
+
+    let mut scenario = valid.clone();
+    scenario.current = Some(CurrentModel {
+      velocity: Vec2D::zeros(),
+      shifts: vec![CurrentShift { time: 10.0, velocity: Vec2D::new(0.0, f64::NAN) }],
+    });
+    assert!(scenario.validate().is_err());
+
+    let mut scenario = valid;
+    scenario.course = vec![Vec2D::new(0.0, -scenario.map.height)];
+    assert!(scenario.validate().is_err());
+  }
+}
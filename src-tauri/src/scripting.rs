@@ -0,0 +1,421 @@
+use std::{
+  collections::BTreeMap,
+  f64::consts::PI,
+  sync::{Arc, Mutex}
+};
+
+use rhai::{
+  module_resolvers::DummyModuleResolver,
+  serde::{from_dynamic, to_dynamic},
+  CallFnOptions, Dynamic, Engine, EvalAltResult, Scope, AST, INT
+};
+use serde::Serialize;
+use tauri::State;
+
+use crate::{
+  geometry::Vec2D,
+  instruments::Readings,
+  mooring::check_line_length,
+  physics::Kinematics,
+  scenario::ShipPreset,
+  ship::ShipControls,
+  simulation::{SimSettings, Simulation}
+};
+
+pub const MAX_SCRIPT_OPERATIONS: u64 = 100_000; // For each call, plenty for a controller but stops runaway loops
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_COLLECTION_SIZE: usize = 10_000;
+
+/// A change to the simulation asked for by a script, made once the script has finished running
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptAction {
+  SetWind { angle: f64, speed: f64 },
+  AddShip { preset: ShipPreset, loc: Vec2D, heading: f64 },
+  PlaceMark(Vec2D),
+  ClearMarks,
+  SetControls { ship: usize, controls: ShipControls },
+  SetThrottle { ship: usize, throttle: f64 },
+  DropAnchor { ship: usize, rode_length: f64 },
+  WeighAnchor { ship: usize },
+}
+
+/// Actions queued by the script being run, and the size of the population once they are made
+#[derive(Debug, Default)]
+struct ActionQueue {
+  actions: Vec<ScriptAction>,
+  ships: usize,
+}
+
+/// What a script sees of a ship
+#[derive(Debug, Clone, Serialize)]
+pub struct ShipView {
+  pub index: usize,
+  pub kinematics: Kinematics,
+  pub controls: ShipControls,
+  /// What the ship's instruments display, or () if it has none
+  pub readings: Option<Readings>,
+}
+
+/// What a script sees of the simulation, passed to hooks as `world` and in scope as `world` when loading
+#[derive(Debug, Clone, Serialize)]
+pub struct WorldView {
+  pub step: u64,
+  pub wind: SimSettings,
+  pub ships: Vec<ShipView>,
+  pub marks: Vec<Vec2D>,
+}
+
+/// A script error that happened while the simulation was stepping, for the frontend to show
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScriptError {
+  /// The ship whose controller failed, or None for the scenario
+  pub ship: Option<usize>,
+  pub message: String,
+}
+
+/// A sandboxed engine whose functions queue actions rather than changing the simulation directly
+struct ScriptEngine {
+  engine: Engine,
+  queue: Arc<Mutex<ActionQueue>>,
+}
+impl ScriptEngine {
+  fn new() -> Self {
+    let queue = Arc::new(Mutex::new(ActionQueue::default()));
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_SCRIPT_OPERATIONS);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.set_max_string_size(MAX_COLLECTION_SIZE);
+    engine.set_max_array_size(MAX_COLLECTION_SIZE);
+    engine.set_max_map_size(MAX_COLLECTION_SIZE);
+    // Scripts can only reach the simulation through the functions below
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+
+    let push = |queue: &Arc<Mutex<ActionQueue>>, action: ScriptAction| queue.lock().unwrap().actions.push(action);
+    let q = queue.clone();
+    engine.register_fn("set_wind", move |angle: Dynamic, speed: Dynamic| -> Result<(), Box<EvalAltResult>> {
+      push(&q, ScriptAction::SetWind { angle: number(angle)?, speed: number(speed)? });
+      Ok(())
+    });
+    let q = queue.clone();
+    engine.register_fn("add_ship", move |kind: &str, x: Dynamic, y: Dynamic, heading: Dynamic| -> Result<INT, Box<EvalAltResult>> {
+      let preset = match ShipPreset::from_name(kind) {
+        Some(preset) => preset,
+        None => return Err(format!("Unknown kind of ship: {}", kind).into()),
+      };
+      let action = ScriptAction::AddShip { preset, loc: Vec2D::new(number(x)?, number(y)?), heading: number(heading)? };
+      let mut queue = q.lock().unwrap();
+      queue.actions.push(action);
+      queue.ships += 1;
+      Ok(queue.ships as INT - 1)
+    });
+    let q = queue.clone();
+    engine.register_fn("place_mark", move |x: Dynamic, y: Dynamic| -> Result<(), Box<EvalAltResult>> {
+      push(&q, ScriptAction::PlaceMark(Vec2D::new(number(x)?, number(y)?)));
+      Ok(())
+    });
+    let q = queue.clone();
+    engine.register_fn("clear_marks", move || push(&q, ScriptAction::ClearMarks));
+    let q = queue.clone();
+    engine.register_fn("set_controls", move |ship: INT, controls: Dynamic| -> Result<(), Box<EvalAltResult>> {
+      let controls: ShipControls = from_dynamic(&controls)?;
+      push(&q, ScriptAction::SetControls { ship: ship_index(ship)?, controls });
+      Ok(())
+    });
+    let q = queue.clone();
+    engine.register_fn("set_throttle", move |ship: INT, throttle: Dynamic| -> Result<(), Box<EvalAltResult>> {
+      push(&q, ScriptAction::SetThrottle { ship: ship_index(ship)?, throttle: number(throttle)? });
+      Ok(())
+    });
+    let q = queue.clone();
+    engine.register_fn("drop_anchor", move |ship: INT, rode_length: Dynamic| -> Result<(), Box<EvalAltResult>> {
+      let rode_length = number(rode_length)?;
+      check_line_length(rode_length)?;
+      push(&q, ScriptAction::DropAnchor { ship: ship_index(ship)?, rode_length });
+      Ok(())
+    });
+    let q = queue.clone();
+    engine.register_fn("weigh_anchor", move |ship: INT| -> Result<(), Box<EvalAltResult>> {
+      push(&q, ScriptAction::WeighAnchor { ship: ship_index(ship)? });
+      Ok(())
+    });
+    engine.register_fn("to_radians", |degrees: Dynamic| -> Result<f64, Box<EvalAltResult>> { Ok(number(degrees)? * PI / 180.0) });
+
+    Self { engine, queue }
+  }
+
+  /// Run something with the queue emptied first, taking whatever actions it queued
+  fn queued<T>(&self, ships: usize, run: impl FnOnce(&Engine) -> Result<T, Box<EvalAltResult>>) -> Result<(T, Vec<ScriptAction>), String> {
+    *self.queue.lock().unwrap() = ActionQueue { actions: Vec::new(), ships };
+    let result = run(&self.engine).map_err(|e| e.to_string());
+    let actions = std::mem::take(&mut self.queue.lock().unwrap().actions);
+    return result.map(|value| (value, actions));
+  }
+}
+
+/// A number from a script, which may have been written as an integer
+fn number(value: Dynamic) -> Result<f64, Box<EvalAltResult>> {
+  if let Some(float) = value.clone().try_cast::<f64>() {
+    return Ok(float);
+  }
+  match value.try_cast::<INT>() {
+    Some(int) => Ok(int as f64),
+    None => Err("Expected a number".into()),
+  }
+}
+
+/// A ship's index from a script, which can't be negative
+fn ship_index(ship: INT) -> Result<usize, Box<EvalAltResult>> {
+  match usize::try_from(ship) {
+    Ok(index) => Ok(index),
+    Err(_) => Err(format!("No ship has the index {}", ship).into()),
+  }
+}
+
+fn has_function(ast: &AST, name: &str, params: usize) -> bool {
+  ast.iter_functions().any(|function| function.name == name && function.params.len() == params)
+}
+
+/// Check a controller script compiles and has a control function, without attaching it to anything
+pub fn check_controller(source: &str) -> Result<(), String> {
+  return Scripts::default().attach_controller(0, source);
+}
+
+/// The scenario and controller scripts loaded into a simulation
+///
+/// A scenario script's top level runs once when loaded, and it may define `fn on_step(world)` to run before every step.
+/// A controller script defines `fn control(ship, world)`, returning new controls for its ship or () to leave them.
+#[derive(Default)]
+pub struct Scripts {
+  engine: Option<ScriptEngine>,
+  scenario: Option<AST>,
+  controllers: BTreeMap<usize, AST>,
+}
+/// The scripts that are loaded, without an engine to run them, to put back later
+#[derive(Clone, Default)]
+pub struct LoadedScripts {
+  scenario: Option<AST>,
+  controllers: BTreeMap<usize, AST>,
+}
+
+impl Clone for Scripts {
+  fn clone(&self) -> Self {
+    // The engine's functions share a queue, so each copy needs its own engine
+    Self {
+      engine: self.engine.as_ref().map(|_| ScriptEngine::new()),
+      scenario: self.scenario.clone(),
+      controllers: self.controllers.clone(),
+    }
+  }
+}
+impl Scripts {
+  pub fn is_empty(&self) -> bool {
+    self.scenario.is_none() && self.controllers.is_empty()
+  }
+
+  fn engine(&mut self) -> &ScriptEngine {
+    self.engine.get_or_insert_with(ScriptEngine::new)
+  }
+
+  /// Run a scenario script's top level, keeping it to run `on_step` before each step if it has one
+  pub fn load_scenario(&mut self, source: &str, world: &WorldView) -> Result<Vec<ScriptAction>, String> {
+    let engine = self.engine();
+    let ast = engine.engine.compile(source).map_err(|e| e.to_string())?;
+    let mut scope = Scope::new();
+    scope.push("world", to_dynamic(world).map_err(|e| e.to_string())?);
+    let ((), actions) = engine.queued(world.ships.len(), |engine| engine.run_ast_with_scope(&mut scope, &ast))?;
+    self.scenario = match has_function(&ast, "on_step", 1) {
+      true => Some(ast),
+      false => None,
+    };
+    return Ok(actions);
+  }
+
+  pub fn attach_controller(&mut self, ship: usize, source: &str) -> Result<(), String> {
+    let ast = self.engine().engine.compile(source).map_err(|e| e.to_string())?;
+    if !has_function(&ast, "control", 2) {
+      return Err(String::from("A controller needs a function control(ship, world)"));
+    }
+    self.controllers.insert(ship, ast);
+    return Ok(());
+  }
+
+  pub fn detach_controller(&mut self, ship: usize) {
+    self.controllers.remove(&ship);
+  }
+
+  pub fn clear(&mut self) {
+    self.scenario = None;
+    self.controllers.clear();
+  }
+
+  pub fn loaded(&self) -> LoadedScripts {
+    LoadedScripts { scenario: self.scenario.clone(), controllers: self.controllers.clone() }
+  }
+
+  /// Put back scripts that were loaded before, keeping the engine
+  pub fn restore(&mut self, loaded: &LoadedScripts) {
+    self.scenario = loaded.scenario.clone();
+    self.controllers = loaded.controllers.clone();
+  }
+
+  /// Run the scenario's hook and every controller, dropping any script that fails so the error isn't repeated every step
+  pub fn before_step(&mut self, world: &WorldView) -> (Vec<ScriptAction>, Vec<ScriptError>) {
+    let mut actions = Vec::new();
+    let mut errors = Vec::new();
+    let world_value = match to_dynamic(world) {
+      Ok(value) => value,
+      Err(error) => return (actions, vec![ScriptError { ship: None, message: error.to_string() }]),
+    };
+    let engine = self.engine.get_or_insert_with(ScriptEngine::new);
+    let options = || CallFnOptions::new().eval_ast(false).rewind_scope(true);
+
+    if let Some(ast) = &self.scenario {
+      let result = engine.queued(world.ships.len(), |engine| {
+        engine.call_fn_with_options::<Dynamic>(options(), &mut Scope::new(), ast, "on_step", (world_value.clone(),))
+      });
+      match result {
+        Ok((_, queued)) => actions.extend(queued),
+        Err(message) => {
+          errors.push(ScriptError { ship: None, message });
+          self.scenario = None;
+        }
+      }
+    }
+
+    self.controllers.retain(|ship, ast| {
+      let view = match world.ships.get(*ship) {
+        Some(view) => view,
+        None => {
+          errors.push(ScriptError { ship: Some(*ship), message: String::from("The ship no longer exists") });
+          return false;
+        }
+      };
+      let result = to_dynamic(view).map_err(|e| e.to_string()).and_then(|view| engine.queued(world.ships.len(), |engine| {
+        engine.call_fn_with_options::<Dynamic>(options(), &mut Scope::new(), ast, "control", (view, world_value.clone()))
+      }));
+      let controls = result.and_then(|(controls, queued)| {
+        actions.extend(queued);
+        match controls.is_unit() {
+          true => Ok(None),
+          false => from_dynamic::<ShipControls>(&controls).map(Some).map_err(|e| e.to_string()),
+        }
+      });
+      match controls {
+        Ok(Some(controls)) => {
+          actions.push(ScriptAction::SetControls { ship: *ship, controls });
+          return true;
+        }
+        Ok(None) => return true,
+        Err(message) => {
+          errors.push(ScriptError { ship: Some(*ship), message });
+          return false;
+        }
+      }
+    });
+    return (actions, errors);
+  }
+}
+
+
+/// Run a scenario script, returning any error in it
+#[tauri::command(rename_all = "snake_case")]
+pub fn load_scenario_script(sim: State<Mutex<Simulation>>, source: String) -> Result<(), String> {
+  let mut sim = sim.lock().unwrap();
+  return sim.load_scenario_script(&source);
+}
+
+/// Steer a ship with a controller script, replacing any controller it already has
+#[tauri::command(rename_all = "snake_case")]
+pub fn attach_controller(sim: State<Mutex<Simulation>>, index: usize, source: String) -> Result<(), String> {
+  let mut sim = sim.lock().unwrap();
+  return sim.get_scripts_mut().attach_controller(index, &source);
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn detach_controller(sim: State<Mutex<Simulation>>, index: usize) {
+  let mut sim = sim.lock().unwrap();
+  sim.get_scripts_mut().detach_controller(index);
+}
+
+#[tauri::command]
+pub fn clear_scripts(sim: State<Mutex<Simulation>>) {
+  let mut sim = sim.lock().unwrap();
+  sim.get_scripts_mut().clear();
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::Scripts;
+  use crate::simulation::Simulation;
+
+  #[test]
+  fn test_runaway_loop_is_stopped() {
+    let mut sim = Simulation::new(0, 0.0, 5.0);
+    let error = sim.load_scenario_script("let n = 0; loop { n += 1; }").unwrap_err();
+    assert!(error.contains("operations"), "{}", error);
+
+    // A controller that never returns is stopped once it has used all its operations, then dropped
+    let mut scripts = Scripts::default();
+    scripts.attach_controller(0, "fn control(ship, world) { loop {} }").unwrap();
+    let (_, errors) = scripts.before_step(&sim.world_view());
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.contains("operations"), "{}", errors[0].message);
+    assert_eq!(errors[0].ship, Some(0));
+    assert!(scripts.is_empty());
+  }
+
+  #[test]
+  fn test_import_fails() {
+    let mut sim = Simulation::new(0, 0.0, 5.0);
+    assert!(sim.load_scenario_script(r#"import "std" as std;"#).is_err());
+    let mut scripts = Scripts::default();
+    scripts.attach_controller(0, r#"fn control(ship, world) { import "std" as std; () }"#).unwrap();
+    let (_, errors) = scripts.before_step(&sim.world_view());
+    assert_eq!(errors.len(), 1);
+  }
+
+  #[test]
+  fn test_bad_controls_detach_controller() {
+    let sim = Simulation::new(0, 0.0, 5.0);
+    let mut scripts = Scripts::default();
+    scripts.attach_controller(0, r#"fn control(ship, world) { #{ rudder_angle: "hard over" } }"#).unwrap();
+    scripts.attach_controller(1, "fn control(ship, world) { let controls = ship.controls; controls.rudder_angle = 0.1; controls }").unwrap();
+    let (actions, errors) = scripts.before_step(&sim.world_view());
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].ship, Some(0));
+    assert_eq!(actions.len(), 1);
+
+    // Only the working controller is still run
+    let (actions, errors) = scripts.before_step(&sim.world_view());
+    assert!(errors.is_empty());
+    assert_eq!(actions.len(), 1);
+  }
+
+  #[test]
+  fn test_negative_ship_index_is_an_error() {
+    let mut sim = Simulation::new(0, 0.0, 5.0);
+    for call in ["set_controls(-1, world.ships[0].controls)", "set_throttle(-1, 0.5)", "drop_anchor(-1, 10.0)", "weigh_anchor(-1)"] {
+      let error = sim.load_scenario_script(call).unwrap_err();
+      assert!(error.contains("No ship has the index -1"), "{}", error);
+    }
+    sim.load_scenario_script("set_throttle(4, 0.5)").unwrap();
+    assert_eq!(sim.get_ship(4).unwrap().controls().throttle, 0.5);
+  }
+
+  #[test]
+  fn test_restoring_a_state_restores_its_scripts() {
+    let mut sim = Simulation::new(0, 0.0, 5.0);
+    sim.get_scripts_mut().attach_controller(0, "fn control(ship, world) { let controls = ship.controls; controls.rudder_angle = 0.1; controls }").unwrap();
+    let state = sim.save_state();
+
+    // Controllers changed after the state was saved are put back as they were
+    sim.get_scripts_mut().detach_controller(0);
+    sim.get_scripts_mut().attach_controller(1, "fn control(ship, world) { let controls = ship.controls; controls.rudder_angle = 0.2; controls }").unwrap();
+    sim.restore_state(&state);
+    sim.step();
+    assert_eq!(sim.get_ship(0).unwrap().controls().rudder_angle, 0.1);
+    assert_eq!(sim.get_ship(1).unwrap().controls().rudder_angle, 0.0);
+  }
+}
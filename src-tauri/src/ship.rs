@@ -1,17 +1,23 @@
 use std::f64::consts::PI;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-  drawing::{AdjustableShipShape, Arrow, PhysicsShapes},
-  geometry::{bound, bound_angle, find_angle, invert_angle, Vec2D},
-  physics::{calculate_aero_force_vecs, calculate_apparent_wind, calculate_apparent_wind_simple, calculate_force, Force},
+  drawing::{Arrow, PhysicsShapes, ShipShape},
+  geometry::{bound, bound_angle, find_angle, invert_angle, Rectangle, Vec2D},
+  mooring::Tether,
+  physics::{calculate_aero_force_vecs, calculate_apparent_wind, calculate_apparent_wind_simple, calculate_force, Force, ForceName, ForceSink, Kinematics, NetForce},
+  propulsion::Engine,
   simulation::DELTA_TIME
 };
 
 // Determined empirically so that the maximum speed is roughly 2x wind speed
 pub const HULL_FRICTION_COEFFICIENT: f64 = 0.007;
 pub const SAIL_AERO_CENTER: f64 = 0.33; // Arbitrarily picked 1/3 of the width from the mast
+pub const MAX_REEF: f64 = 1.0; // Fraction of the sail that can be reefed, 1.0 being fully furled
+pub const MAX_SAIL_TWIST: f64 = PI / 12.0; // Twist between the foot and head of the sail with no vang tension
+pub const CREW_MASS: f64 = 160.0; // kg, two adult sailors
+pub const GRAVITY: f64 = 9.81; // m / s^2
 
 pub const DENSITY_AIR: f64 = 1.225; // kg / m^3
 pub const DENSITY_WATER: f64 = 1027.0; // kg / m^3
@@ -19,12 +25,100 @@ pub const DENSITY_WOOD: f64 = 750.0; // kg / m^3, solid white oak
 pub const DENSITY_SAIL: f64 = 0.237; // kg / m^2 canvas (7 oz/yd^2)
 
 
-pub trait Ship {
-  /// Update the physical state of the ship
-  fn update(&mut self, wind_angle: f64, wind_speed: f64);
+pub trait Ship: Send {
+  /// Update the physical state of the ship, including any forces from outside the ship such as lines to other ships
+  fn update(&mut self, wind_angle: f64, wind_speed: f64, current: Vec2D, external_forces: &[Force]);
 
-  /// Calculate all of the forces acting on the ship
-  fn forces(&mut self, wind_angle: f64, wind_speed: f64) -> Vec<Force>;
+  /// Work out the forces acting on the ship, with the water moving over the ground at the current's velocity
+  fn add_forces(&self, wind_angle: f64, wind_speed: f64, current: Vec2D, sink: &mut dyn ForceSink);
+
+  /// Every force acting on the ship by name, for debugging and drawing
+  fn forces(&self, wind_angle: f64, wind_speed: f64, current: Vec2D) -> Vec<Force> {
+    let mut forces = Vec::new();
+    self.add_forces(wind_angle, wind_speed, current, &mut forces);
+    return forces;
+  }
+
+  /// The current motion of the ship
+  fn kinematics(&self) -> Kinematics;
+
+  fn controls(&self) -> &ShipControls;
+
+  /// Replace the ship's controls, bounded to what the ship can physically do
+  fn set_controls(&mut self, controls: ShipControls);
+
+  /// Anchor rodes and mooring lines holding the ship in place
+  fn tethers(&self) -> &[Tether];
+
+  fn tethers_mut(&mut self) -> &mut Vec<Tether>;
+
+  /// The engine, for ships that have one
+  fn engine(&self) -> Option<&Engine>;
+
+  /// Where lines are made fast at the bow, relative to the center of the ship
+  fn bow_offset(&self) -> Vec2D;
+
+  /// Where lines are made fast at the stern, relative to the center of the ship
+  fn stern_offset(&self) -> Vec2D;
+
+  /// The outline of the ship's hull, for picking and collisions
+  fn hull(&self) -> Rectangle;
+
+  /// Whether the given point lands on the ship's hull
+  fn contains(&self, point: Vec2D) -> bool {
+    self.hull().contains(point)
+  }
+
+  /// Distance from the center of the ship to the furthest point of its hull
+  fn radius(&self) -> f64 {
+    self.hull().radius()
+  }
+
+  /// The drawable outline of the ship
+  fn shape(&self) -> ShipShape;
+
+  /// The full state of the ship, for display in the frontend
+  fn to_json(&self) -> serde_json::Value;
+
+  /// The parts of the ship's state that change as it sails
+  fn state(&self) -> ShipState;
+
+  /// Put the ship back into a previously taken state
+  fn restore(&mut self, state: &ShipState);
+
+  /// A copy of the whole ship, for snapshots of the simulation
+  fn clone_box(&self) -> Box<dyn Ship>;
+
+  /// Radians the ship leans to starboard, estimated since the simulation is flat, or zero for ships that don't heel
+  fn heel(&self, _wind_angle: f64, _wind_speed: f64) -> f64 {
+    0.0
+  }
+
+  /// Whether the ship's hull can touch other ships, which ghosts only shown for comparison can't
+  fn solid(&self) -> bool {
+    true
+  }
+}
+impl Clone for Box<dyn Ship> {
+  fn clone(&self) -> Self {
+    self.clone_box()
+  }
+}
+
+/// The changing state of a ship at one moment, small enough to record every step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShipState {
+  pub kinematics: Kinematics,
+  pub sail_angles: Vec<f64>,
+  pub controls: ShipControls,
+  #[serde(default)]
+  pub tethers: Vec<Tether>,
+  /// Fuel left in the engine, for ships that have one
+  #[serde(default)]
+  pub fuel: Option<f64>,
+  /// Seconds along the recorded track, for ghost boats
+  #[serde(default)]
+  pub track_time: Option<f64>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize)]
@@ -37,6 +131,17 @@ impl SailSpecs {
   pub fn new(mast_offset: f64, width: f64, height: f64) -> Self {
     Self { mast_offset, width, height }
   }
+
+  /// The effective dimensions of the sail when the given fraction of its area is reefed (0.0 is fully hoisted)
+  pub fn reefed(&self, reef_amount: f64) -> Self {
+    // Both sides shrink by the same factor so the sail keeps its shape, and the area falls in step with the reef
+    let scale = (1.0 - reef_amount.clamp(0.0, MAX_REEF)).sqrt();
+    Self::new(self.mast_offset, self.width * scale, self.height * scale)
+  }
+
+  pub fn area(&self) -> f64 {
+    self.width * self.height * 0.5 // Half because triangle
+  }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
@@ -69,6 +174,14 @@ impl ShipSpecs {
       1.0, 1.0, vec![SailSpecs::new(4.0, 7.0, 10.0)]
     )
   }
+  /// A launch with no sails, to be driven by an engine
+  pub fn motor_boat() -> Self {
+    Self::new(
+      2.5, 8.0, 0.5, 0.05,
+      0.5, 1.0, 0.3,
+      0.8, 0.8, vec![]
+    )
+  }
 
   pub fn validate(&self) -> Result<(), String> {
     // All dimensions are positive, non-zero values
@@ -112,18 +225,8 @@ impl ShipSpecs {
 
   /// The mass of the ship
   pub fn calculate_mass(&self) -> f64 {
-    let mass_hull = DENSITY_WOOD * self.hull_thickness * (
-      self.hull_length * self.hull_width // Bottom
-      + 2.0 * self.hull_width * self.hull_depth // Two Sides
-      + 2.0 * self.hull_length * self.hull_depth // Front & Back
-    );
-    let mass_sails: f64 = self.sails.iter().map(|sail| {
-      let sail_mass = DENSITY_SAIL * sail.width * sail.height * 0.5; // Half because triangle
-      let sail_thickness = 0.01 * sail.width * sail.height; // Guessing a mast needs to be about 1 cm thick for every square meter of sail
-      let mast_mass = DENSITY_WOOD * sail.height * sail_thickness * sail_thickness;
-      return sail_mass + mast_mass;
-    }).sum();
-    return mass_hull + mass_sails;
+    let mass_hull = calculate_hull_mass(self.hull_length, self.hull_width, self.hull_depth, self.hull_thickness);
+    return mass_hull + calculate_rig_mass(&self.sails);
   }
 
   /// The weight that the ship can carry without sinking (ship is not buoyant if less than 0)
@@ -134,10 +237,304 @@ impl ShipSpecs {
   }
 }
 
+/// The mass of an open wooden hull
+pub fn calculate_hull_mass(length: f64, width: f64, depth: f64, thickness: f64) -> f64 {
+  DENSITY_WOOD * thickness * (
+    length * width // Bottom
+    + 2.0 * width * depth // Two Sides
+    + 2.0 * length * depth // Front & Back
+  )
+}
+
+/// The mass of the sails and the masts holding them up
+pub fn calculate_rig_mass(sails: &[SailSpecs]) -> f64 {
+  sails.iter().map(|sail| {
+    let sail_mass = DENSITY_SAIL * sail.area();
+    let sail_thickness = 0.01 * sail.width * sail.height; // Guessing a mast needs to be about 1 cm thick for every square meter of sail
+    let mast_mass = DENSITY_WOOD * sail.height * sail_thickness * sail_thickness;
+    return sail_mass + mast_mass;
+  }).sum()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShipControls {
+  pub mainsheet_lengths: Vec<f64>,
+  pub reef_amounts: Vec<f64>,
+  /// Lateral position of each sail's sheeting point, from -1.0 (starboard rail) to 1.0 (port rail)
+  pub traveller_positions: Vec<f64>,
+  /// Tension on each boom vang, from 0.0 (maximum twist) to 1.0 (no twist)
+  pub vang_tensions: Vec<f64>,
+  /// Location of the crew relative to the center of the hull
+  pub crew_position: Vec2D,
+  pub rudder_angle: f64,
+  /// Engine throttle, from -1.0 (full astern) to 1.0 (full ahead)
+  pub throttle: f64,
+}
+impl ShipControls {
+  pub fn new(mainsheet_lengths: Vec<f64>, rudder_angle: f64) -> Self {
+    let sail_count = mainsheet_lengths.len();
+    Self {
+      mainsheet_lengths,
+      reef_amounts: vec![0.0; sail_count],
+      traveller_positions: vec![0.0; sail_count],
+      vang_tensions: vec![1.0; sail_count],
+      crew_position: Vec2D::zeros(),
+      rudder_angle,
+      throttle: 0.0,
+    }
+  }
+
+  /// Clamp every control to its physically possible range on a boat of the given size, with one of each sail control
+  /// for every sail
+  pub fn bounded(mut self, sail_count: usize, length: f64, beam: f64) -> Self {
+    self.mainsheet_lengths.resize(sail_count, 0.0);
+    self.reef_amounts.resize(sail_count, 0.0);
+    self.traveller_positions.resize(sail_count, 0.0);
+    self.vang_tensions.resize(sail_count, 1.0);
+    self.reef_amounts.iter_mut().for_each(|reef| *reef = reef.clamp(0.0, MAX_REEF));
+    self.traveller_positions.iter_mut().for_each(|pos| *pos = pos.clamp(-1.0, 1.0));
+    self.vang_tensions.iter_mut().for_each(|vang| *vang = vang.clamp(0.0, 1.0));
+    self.throttle = self.throttle.clamp(-1.0, 1.0);
+    let half_length = length * 0.5;
+    let half_width = beam * 0.5;
+    self.crew_position = Vec2D::new(
+      self.crew_position.x.clamp(-half_length, half_length),
+      self.crew_position.y.clamp(-half_width, half_width)
+    );
+    return self;
+  }
+
+  pub fn mainsheet_length(&self, sail_index: usize) -> f64 {
+    self.mainsheet_lengths.get(sail_index).copied().unwrap_or(0.0)
+  }
+
+  pub fn reef_amount(&self, sail_index: usize) -> f64 {
+    self.reef_amounts.get(sail_index).copied().unwrap_or(0.0)
+  }
+
+  pub fn traveller_position(&self, sail_index: usize) -> f64 {
+    self.traveller_positions.get(sail_index).copied().unwrap_or(0.0)
+  }
+
+  pub fn vang_tension(&self, sail_index: usize) -> f64 {
+    self.vang_tensions.get(sail_index).copied().unwrap_or(1.0)
+  }
+}
+
+/// Calculate the angle a sail swings to based on the apparent wind angle and how it is trimmed
+pub fn trim_sail(sail: &SailSpecs, sail_index: usize, controls: &ShipControls, beam: f64, heading: f64, previous_angle: f64, apparent_wind_angle: f64) -> f64 {
+  // The traveller moves the sheeting point off the centerline, shifting the range the boom can swing through
+  let traveller_offset = controls.traveller_position(sail_index) * beam * 0.5;
+  let sheeting_point = Vec2D::new(sail.width, -traveller_offset);
+  let traveller_angle = sheeting_point.to_angle();
+  let sheeting_distance = sheeting_point.magnitude();
+  let mainsheet_length = controls.mainsheet_length(sail_index)
+    .clamp(sheeting_distance - sail.width, sheeting_distance + sail.width);
+  let max_sail_angle = find_angle(sail.width, sheeting_distance, mainsheet_length);
+  let hull_relative_apparent_wind_angle = bound_angle(invert_angle(apparent_wind_angle) - heading);
+  return match bound_angle(hull_relative_apparent_wind_angle - traveller_angle).abs() <= max_sail_angle {
+    // Sail is luffing
+    true => hull_relative_apparent_wind_angle,
+    // Sail is taut, and stays leeward until forced to switch sides
+    false => traveller_angle + max_sail_angle * bound_angle(hull_relative_apparent_wind_angle - previous_angle).signum()
+  };
+}
+
+/// The lift and drag on one sail
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SailForce {
+  pub center: Vec2D,
+  pub lift: Vec2D,
+  pub drag: Vec2D,
+}
+
+/// The wind over one sail as it is felt aboard, scaled to a single step
+fn sail_apparent_wind(kinematics: Kinematics, sail: &SailSpecs, wind_angle: f64, wind_speed: f64) -> Vec2D {
+  calculate_apparent_wind(
+    kinematics.vel, kinematics.rot_vel, kinematics.heading, sail.mast_offset,
+    wind_angle, wind_speed
+  ).scale(DELTA_TIME)
+}
+
+/// Swing every sail to where the wind and its sheet leave it, keeping its angle if there is no apparent wind
+pub fn trim_sails(kinematics: Kinematics, sails: &[SailSpecs], controls: &ShipControls, beam: f64, sail_angles: &mut [f64], wind_angle: f64, wind_speed: f64) {
+  for sail_index in 0..sails.len() {
+    // A reefed sail has a shorter boom, so the same length of sheet lets it swing further
+    let sail = sails[sail_index].reefed(controls.reef_amount(sail_index));
+    let apparent_wind = sail_apparent_wind(kinematics, &sail, wind_angle, wind_speed);
+    if apparent_wind.magnitude() != 0.0 {
+      sail_angles[sail_index] = trim_sail(
+        &sail, sail_index, controls, beam, kinematics.heading,
+        sail_angles[sail_index], apparent_wind.to_angle()
+      );
+    }
+  }
+}
+
+/// Calculate the force on one sail set at the given angle, or nothing if there is no apparent wind to fill it
+pub fn sail_force(kinematics: Kinematics, sails: &[SailSpecs], sail_index: usize, controls: &ShipControls, sail_angle: f64, wind_angle: f64, wind_speed: f64) -> Option<SailForce> {
+  let sail = sails[sail_index].reefed(controls.reef_amount(sail_index));
+  let sail_area = sail.area();
+  let apparent_wind = sail_apparent_wind(kinematics, &sail, wind_angle, wind_speed);
+  if apparent_wind.magnitude() == 0.0 {
+    return None;
+  }
+  let apparent_wind_angle = apparent_wind.to_angle();
+  let aoa = bound(kinematics.heading + sail_angle - apparent_wind_angle, 0.0, PI);
+  // Twist opens the head of the sail, so on average the sail meets the wind at a shallower angle
+  let twist = (1.0 - controls.vang_tension(sail_index)) * MAX_SAIL_TWIST;
+  let aoa = if aoa < PI * 0.5 { f64::max(aoa - twist * 0.5, 0.0) } else { f64::min(aoa + twist * 0.5, PI) };
+  let (lift, drag) = calculate_aero_force_vecs(aoa, sail_area, DENSITY_AIR, apparent_wind);
+  let center = kinematics.loc
+    + Vec2D::new(sail.mast_offset, 0.0).rotate(kinematics.heading)
+    + Vec2D::new(-sail.width*SAIL_AERO_CENTER, 0.0).rotate(kinematics.heading + sail_angle);
+  return Some(SailForce { center, lift, drag });
+}
+
+/// Calculate the lift and drag on every sail at the angles they are set to
+pub fn add_sail_forces(kinematics: Kinematics, sails: &[SailSpecs], controls: &ShipControls, sail_angles: &[f64], wind_angle: f64, wind_speed: f64, sink: &mut dyn ForceSink) {
+  for sail_index in 0..sails.len() {
+    let force = sail_force(kinematics, sails, sail_index, controls, sail_angles[sail_index], wind_angle, wind_speed);
+    if let Some(force) = force {
+      sink.add(ForceName::SailLift(sail_index), force.center, force.lift);
+      sink.add(ForceName::SailDrag(sail_index), force.center, force.drag);
+    }
+  }
+}
+
+/// What sets one kind of sailing ship apart from another: its hulls and the foils under them
+pub trait HullSpecs: Clone + PartialEq + Serialize + Send + 'static {
+  fn sails(&self) -> &[SailSpecs];
+
+  /// The mass of the ship without its crew
+  fn calculate_mass(&self) -> f64;
+
+  /// Overall length, from bow to stern
+  fn length(&self) -> f64;
+
+  /// Overall width, which the travellers run across and the crew can move about in
+  fn beam(&self) -> f64;
+
+  /// Work out the forces of the water on the hulls, keels and rudders
+  fn add_water_forces(&self, kinematics: Kinematics, controls: &ShipControls, total_mass: f64, current: Vec2D, sink: &mut dyn ForceSink);
+
+  /// The drawable outline of a ship built on these hulls
+  fn shape(ship: &SailingShip<Self>) -> ShipShape;
+
+  /// Height of the deck the masts are stepped on, above where the water pushes sideways on the hulls
+  fn deck_height(&self) -> f64;
+
+  /// Moment in N m it takes to lay the ship on its side, with smaller moments heeling it by the sine of their share of it
+  fn righting_moment(&self, total_mass: f64) -> f64;
+}
+
+impl HullSpecs for ShipSpecs {
+  fn sails(&self) -> &[SailSpecs] {
+    &self.sails
+  }
+
+  fn calculate_mass(&self) -> f64 {
+    ShipSpecs::calculate_mass(self)
+  }
+
+  fn length(&self) -> f64 {
+    self.hull_length
+  }
+
+  fn beam(&self) -> f64 {
+    self.hull_width
+  }
+
+  fn add_water_forces(&self, kinematics: Kinematics, controls: &ShipControls, total_mass: f64, current: Vec2D, sink: &mut dyn ForceSink) {
+    let Kinematics { loc, vel, rot_vel, heading } = kinematics;
+    let water_vel = (current - vel).scale(DELTA_TIME);
+
+    // Calculate keel forces, split between the front and back portions of the keel to account for different rotations
+    let (fore_keel_length, aft_keel_length) =
+      if self.keel_start_offset <= 0.0 {
+        (0.0, self.keel_length)
+      } else {
+        let aft = f64::max(0.0, self.keel_length - self.keel_start_offset);
+        (self.keel_length - aft, aft)
+      };
+
+    if fore_keel_length > 0.0 {
+      let keel_center = self.keel_start_offset - fore_keel_length*0.5;
+      let water_rot_vel = Vec2D::new(0.0, -rot_vel * DELTA_TIME * keel_center).rotate(heading);
+      let rel_water_vel = water_vel + water_rot_vel;
+      let aoa: f64 = bound(heading - rel_water_vel.to_angle(), 0.0, PI);
+      let (lift, drag) = calculate_aero_force_vecs(aoa, self.keel_height*fore_keel_length, DENSITY_WATER, rel_water_vel);
+      let keel_loc = Vec2D::new(keel_center, 0.0).rotate(heading) + loc;
+      sink.add(ForceName::ForeKeelLift, keel_loc, lift);
+      sink.add(ForceName::ForeKeelDrag, keel_loc, drag);
+    }
+
+    if aft_keel_length > 0.0 {
+      let keel_end_offset = self.keel_start_offset - self.keel_length;
+      let keel_center = keel_end_offset + aft_keel_length*0.5;
+      let water_rot_vel = Vec2D::new(0.0, -rot_vel * DELTA_TIME * keel_center).rotate(heading);
+      let rel_water_vel = water_vel + water_rot_vel;
+      let aoa: f64 = bound(heading - rel_water_vel.to_angle(), 0.0, PI);
+      let (lift, drag) = calculate_aero_force_vecs(aoa, self.keel_height*aft_keel_length, DENSITY_WATER, rel_water_vel);
+      let keel_loc = Vec2D::new(keel_center, 0.0).rotate(heading) + loc;
+      sink.add(ForceName::AftKeelLift, keel_loc, lift);
+      sink.add(ForceName::AftKeelDrag, keel_loc, drag);
+    }
+
+    // Calculate rudder forces
+    let water_rot_vel = Vec2D::new(0.0, rot_vel * DELTA_TIME * self.hull_length * 0.5).rotate(heading);
+    let rel_water_vel = water_vel + water_rot_vel;
+    let aoa: f64 = bound(heading + controls.rudder_angle - rel_water_vel.to_angle(), 0.0, PI);
+    let (lift, drag) = calculate_aero_force_vecs(aoa, self.rudder_height * self.rudder_length, DENSITY_WATER, rel_water_vel);
+    let rudder_loc = Vec2D::new(-self.hull_length * 0.5, 0.0).rotate(heading) + loc;
+    sink.add(ForceName::RudderLift, rudder_loc, lift);
+    sink.add(ForceName::RudderDrag, rudder_loc, drag);
+
+    // Calculate hull drag forces, with the crew's weight trimming the bow or stern down
+    // and heeling the hull so that its immersed shape (and drag) shifts to one side
+    let trim = (controls.crew_position.x / (self.hull_length * 0.5)) * CREW_MASS / total_mass;
+    let heel_offset = controls.crew_position.y * CREW_MASS / total_mass;
+    let bow_water_rot_vel = Vec2D::new(0.0, -rot_vel * DELTA_TIME * self.hull_length * 0.25).rotate(heading);
+    let bow_water_vel = water_vel + bow_water_rot_vel;
+    let aoa: f64 = bound(heading - bow_water_vel.to_angle(), 0.0, PI);
+    let apparent_width = f64::cos(aoa).abs() * self.hull_width + f64::sin(aoa).abs() * self.hull_length * 0.5;
+    let wetted_area = self.hull_depth * (1.0 + trim) * apparent_width;
+    let drag_magnitude = calculate_force(HULL_FRICTION_COEFFICIENT, wetted_area, DENSITY_WATER, bow_water_vel.magnitude());
+    let drag = bow_water_vel.unit().scale(drag_magnitude);
+    let offset = Vec2D::new(self.hull_length * 0.5, heel_offset).rotate(heading);
+    sink.add(ForceName::BowDrag, loc + offset, drag);
+
+    let stern_water_rot_vel = Vec2D::new(0.0, rot_vel * DELTA_TIME * self.hull_length * 0.25).rotate(heading);
+    let stern_water_vel = water_vel + stern_water_rot_vel;
+    let aoa: f64 = bound(heading - stern_water_vel.to_angle(), 0.0, PI);
+    let apparent_width = f64::cos(aoa).abs() * self.hull_width + f64::sin(aoa).abs() * self.hull_length * 0.5;
+    let wetted_area = self.hull_depth * (1.0 - trim) * apparent_width;
+    let drag_magnitude = calculate_force(HULL_FRICTION_COEFFICIENT, wetted_area, DENSITY_WATER, stern_water_vel.magnitude());
+    let drag = stern_water_vel.unit().scale(drag_magnitude);
+    let offset = Vec2D::new(-self.hull_length * 0.5, heel_offset).rotate(heading);
+    sink.add(ForceName::SternDrag, loc + offset, drag);
+  }
+
+  fn shape(ship: &AdjustableShip) -> ShipShape {
+    ShipShape::from_adjustable(ship)
+  }
+
+  fn deck_height(&self) -> f64 {
+    self.hull_depth * 0.5
+  }
+
+  fn righting_moment(&self, total_mass: f64) -> f64 {
+    // Metacentric height of a box shaped hull, B^2 / 12T
+    let metacentric_height = self.hull_width.powi(2) / (12.0 * self.hull_depth);
+    return total_mass * GRAVITY * metacentric_height;
+  }
+}
+
+/// A sailing ship built on the given hulls, with the sails, crew, engine and tethers every kind of ship shares
 #[derive(Debug, Clone, PartialEq, Serialize)]
-pub struct AdjustableShip {
+pub struct SailingShip<S> {
   // Specifications of the ship
-  pub specs: ShipSpecs,
+  pub specs: S,
 
   // Indirectly controlled state
   pub loc: Vec2D,
@@ -147,169 +544,213 @@ pub struct AdjustableShip {
   pub sail_angles: Vec<f64>,
 
   // Directly controlled state
-  pub mainsheet_lengths: Vec<f64>,
-  pub rudder_angle: f64,
+  pub controls: ShipControls,
+
+  // Optional components
+  pub engine: Option<Engine>,
+  pub tethers: Vec<Tether>,
 }
-impl AdjustableShip {
-  pub fn new(specs: ShipSpecs, loc: Vec2D, vel: Vec2D, rot_vel: f64, heading: f64, mainsheet_lengths: Vec<f64>, rudder_angle: f64) -> Self {
-    let sail_angles: Vec<f64> = vec![0.0; specs.sails.len()];
-    Self { specs, loc, vel, rot_vel, heading, mainsheet_lengths, sail_angles, rudder_angle }
-  }
-
-  /// Calculate the angle the sail should be based on the apparent wind angle
-  fn set_sail_angle(&mut self, sail_index: usize, apparent_wind_angle: f64) -> f64 {
-    let sail_spec = &self.specs.sails[sail_index];
-    let max_sail_angle = find_angle(sail_spec.width, sail_spec.width, self.mainsheet_lengths[sail_index]);
-    let hull_relative_apparent_wind_angle = bound_angle(invert_angle(apparent_wind_angle) - self.heading);
-    let sail_angle = match hull_relative_apparent_wind_angle.abs() <= max_sail_angle {
-      // Sail is luffing
-      true => hull_relative_apparent_wind_angle,
-      // Sail is taut, and stays leeward until forced to switch sides
-      false => max_sail_angle * bound_angle(hull_relative_apparent_wind_angle - self.sail_angles[sail_index]).signum()
-    };
-    self.sail_angles[sail_index] = sail_angle;
-    return sail_angle;
+impl<S: HullSpecs> SailingShip<S> {
+  pub fn new(specs: S, loc: Vec2D, vel: Vec2D, rot_vel: f64, heading: f64, controls: ShipControls) -> Self {
+    let sail_angles: Vec<f64> = vec![0.0; specs.sails().len()];
+    Self { specs, loc, vel, rot_vel, heading, sail_angles, controls, engine: None, tethers: Vec::new() }
   }
-}
-impl Ship for AdjustableShip {
-  fn update(&mut self, wind_angle: f64, wind_speed: f64) {
-    let inverse_mass = 1.0 / (self.specs.calculate_mass());
 
-    self.forces(wind_angle, wind_speed).iter().for_each(|force| {
-      // A force always changes the velocity
-      self.vel = self.vel + force.vec.scale(inverse_mass);
+  pub fn with_engine(mut self, engine: Engine) -> Self {
+    self.engine = Some(engine);
+    self
+  }
 
-      // Rotate force vector so that x component is in line with center of mass
-      let offset = force.loc - self.loc;
-      let loc_rot = offset.rotate(-offset.to_angle());
-      let vec_rot = force.vec.rotate(-offset.to_angle());
-      // Then only the vector's y component contributes to rotation
-      let torque = vec_rot.y * loc_rot.magnitude();
-      // Just assume the ship is a point mass even though it obviously isn't
-      self.rot_vel = self.rot_vel + torque * inverse_mass;
-    });
+  /// The sail as it is currently set, accounting for any reefing
+  pub fn effective_sail(&self, sail_index: usize) -> SailSpecs {
+    self.specs.sails()[sail_index].reefed(self.controls.reef_amount(sail_index))
+  }
 
-    self.loc = self.loc + self.vel.scale(DELTA_TIME);
-    self.heading = bound_angle(self.heading + self.rot_vel * DELTA_TIME);
+  /// The mass of the ship including the crew
+  pub fn total_mass(&self) -> f64 {
+    self.specs.calculate_mass() + CREW_MASS
   }
 
-  fn forces(&mut self, wind_angle: f64, wind_speed: f64) -> Vec<Force> {
-    let mut forces = Vec::new();
+  /// The center of mass, shifted from the center of the ship by the weight of the crew
+  pub fn center_of_mass(&self) -> Vec2D {
+    let crew_offset = self.controls.crew_position.scale(CREW_MASS / self.total_mass());
+    self.loc + crew_offset.rotate(self.heading)
+  }
+
+  fn set_kinematics(&mut self, kinematics: Kinematics) {
+    self.loc = kinematics.loc;
+    self.vel = kinematics.vel;
+    self.rot_vel = kinematics.rot_vel;
+    self.heading = kinematics.heading;
+  }
+
+  /// Let the sails swing to the apparent wind before working out the forces on them
+  pub fn trim_sails(&mut self, wind_angle: f64, wind_speed: f64) {
+    trim_sails(
+      self.kinematics(), self.specs.sails(), &self.controls, self.specs.beam(),
+      &mut self.sail_angles, wind_angle, wind_speed
+    );
+  }
+}
+impl<S: HullSpecs> Ship for SailingShip<S> {
+  fn update(&mut self, wind_angle: f64, wind_speed: f64, current: Vec2D, external_forces: &[Force]) {
+    self.trim_sails(wind_angle, wind_speed);
+    let mass = self.total_mass();
+    let mut net = NetForce::new(self.center_of_mass());
+    self.add_forces(wind_angle, wind_speed, current, &mut net);
+    external_forces.iter().for_each(|force| net.add_force(*force));
+
+    let mut kinematics = self.kinematics();
+    self.tethers.iter_mut().for_each(|tether| tether.slip(kinematics));
+    kinematics.apply_net_force(net, mass);
+    self.set_kinematics(kinematics);
+
+    if let Some(engine) = &mut self.engine {
+      engine.burn(self.controls.throttle);
+    }
+  }
+
+  fn add_forces(&self, wind_angle: f64, wind_speed: f64, current: Vec2D, sink: &mut dyn ForceSink) {
+    let kinematics = self.kinematics();
+
     // Sail forces
     if wind_speed != 0.0 || self.vel.magnitude() != 0.0 || self.rot_vel != 0.0 {
-      for sail_index in 0..self.specs.sails.len() {
-        let sail = &self.specs.sails[sail_index];
-        let sail_area = sail.width * sail.height * 0.5; // Half because triangle
-        let apparent_wind = calculate_apparent_wind(
-          self.vel, self.rot_vel, self.heading, sail.mast_offset,
-          wind_angle, wind_speed
-        ).scale(DELTA_TIME);
-        let apparent_wind_angle = apparent_wind.to_angle();
-        let sail_angle = self.set_sail_angle(sail_index, apparent_wind_angle);
-        let aoa = bound(self.heading + sail_angle - apparent_wind_angle, 0.0, PI);
-        let (lift, drag) = calculate_aero_force_vecs(aoa, sail_area, DENSITY_AIR, apparent_wind);
-        let sail_center = self.loc
-          + Vec2D::new(self.specs.sails[sail_index].mast_offset, 0.0).rotate(self.heading)
-          + Vec2D::new(-self.specs.sails[sail_index].width*SAIL_AERO_CENTER, 0.0).rotate(self.heading + sail_angle);
-        forces.push(Force::new(String::from(format!("Sail {} Lift", sail_index)), sail_center, lift));
-        forces.push(Force::new(String::from(format!("Sail {} Drag", sail_index)), sail_center, drag));
-      }
+      add_sail_forces(kinematics, self.specs.sails(), &self.controls, &self.sail_angles, wind_angle, wind_speed, sink);
     }
 
-    let water_vel = self.vel.scale(-1.0 * DELTA_TIME);
-    if self.vel.magnitude() != 0.0 || self.rot_vel != 0.0 {
-      // Calculate keel forces, split between the front and back portions of the keel to account for different rotations
-      let (fore_keel_length, aft_keel_length) =
-        if self.specs.keel_start_offset <= 0.0 {
-          (0.0, self.specs.keel_length)
-        } else {
-          let aft = f64::max(0.0, self.specs.keel_length - self.specs.keel_start_offset);
-          (self.specs.keel_length - aft, aft)
-        };
-
-      if fore_keel_length > 0.0 {
-        let keel_center = self.specs.keel_start_offset - fore_keel_length*0.5;
-        let water_rot_vel = Vec2D::new(0.0, -self.rot_vel * DELTA_TIME * keel_center).rotate(self.heading);
-        let rel_water_vel = water_vel + water_rot_vel;
-        let aoa: f64 = bound(self.heading - rel_water_vel.to_angle(), 0.0, PI);
-        let (lift, drag) = calculate_aero_force_vecs(aoa, self.specs.keel_height*fore_keel_length, DENSITY_WATER, rel_water_vel);
-        let keel_loc = Vec2D::new(keel_center, 0.0).rotate(self.heading) + self.loc;
-        forces.push(Force::new(String::from("Fore Keel Lift"), keel_loc, lift));
-        forces.push(Force::new(String::from("Fore Keel Drag"), keel_loc, drag));
-      }
+    if (current - self.vel).magnitude() != 0.0 || self.rot_vel != 0.0 {
+      self.specs.add_water_forces(kinematics, &self.controls, self.total_mass(), current, sink);
+    }
 
-      if aft_keel_length > 0.0 {
-        let keel_end_offset = self.specs.keel_start_offset - self.specs.keel_length;
-        let keel_center = keel_end_offset + aft_keel_length*0.5;
-        let water_rot_vel = Vec2D::new(0.0, -self.rot_vel * DELTA_TIME * keel_center).rotate(self.heading);
-        let rel_water_vel = water_vel + water_rot_vel;
-        let aoa: f64 = bound(self.heading - rel_water_vel.to_angle(), 0.0, PI);
-        let (lift, drag) = calculate_aero_force_vecs(aoa, self.specs.keel_height*aft_keel_length, DENSITY_WATER, rel_water_vel);
-        let keel_loc = Vec2D::new(keel_center, 0.0).rotate(self.heading) + self.loc;
-        forces.push(Force::new(String::from("Aft Keel Lift"), keel_loc, lift));
-        forces.push(Force::new(String::from("Aft Keel Drag"), keel_loc, drag));
-      }
+    if let Some(engine) = &self.engine {
+      engine.add_forces(kinematics, self.controls.throttle, sink);
+    }
 
-      // Calculate rudder forces
-      let water_rot_vel = Vec2D::new(0.0, self.rot_vel * DELTA_TIME * self.specs.hull_length * 0.5).rotate(self.heading);
-      let rel_water_vel = water_vel + water_rot_vel;
-      let aoa: f64 = bound(self.heading + self.rudder_angle - rel_water_vel.to_angle(), 0.0, PI);
-      let (lift, drag) = calculate_aero_force_vecs(aoa, self.specs.rudder_height * self.specs.rudder_length, DENSITY_WATER, rel_water_vel);
-      let rudder_loc = Vec2D::new(-self.specs.hull_length * 0.5, 0.0).rotate(self.heading) + self.loc;
-      forces.push(Force::new(String::from("Rudder Lift"), rudder_loc, lift));
-      forces.push(Force::new(String::from("Rudder Drag"), rudder_loc, drag));
-
-      // Calculate hull drag forces
-      let bow_water_rot_vel = Vec2D::new(0.0, -self.rot_vel * DELTA_TIME * self.specs.hull_length * 0.25).rotate(self.heading);
-      let bow_water_vel = water_vel + bow_water_rot_vel;
-      let aoa: f64 = bound(self.heading - bow_water_vel.to_angle(), 0.0, PI);
-      let apparent_width = f64::cos(aoa).abs() * self.specs.hull_width + f64::sin(aoa).abs() * self.specs.hull_length * 0.5;
-      let wetted_area = self.specs.hull_depth * apparent_width;
-      let drag_magnitude = calculate_force(HULL_FRICTION_COEFFICIENT, wetted_area, DENSITY_WATER, bow_water_vel.magnitude());
-      let drag = bow_water_vel.unit().scale(drag_magnitude);
-      let offset = Vec2D::from_angle(self.heading).scale(self.specs.hull_length * 0.5);
-      forces.push(Force::new(String::from("Bow Drag"), self.loc + offset, drag));
-
-      let stern_water_rot_vel = Vec2D::new(0.0, self.rot_vel * DELTA_TIME * self.specs.hull_length * 0.25).rotate(self.heading);
-      let stern_water_vel = water_vel + stern_water_rot_vel;
-      let aoa: f64 = bound(self.heading - stern_water_vel.to_angle(), 0.0, PI);
-      let apparent_width = f64::cos(aoa).abs() * self.specs.hull_width + f64::sin(aoa).abs() * self.specs.hull_length * 0.5;
-      let wetted_area = self.specs.hull_depth * apparent_width;
-      let drag_magnitude = calculate_force(HULL_FRICTION_COEFFICIENT, wetted_area, DENSITY_WATER, stern_water_vel.magnitude());
-      let drag = stern_water_vel.unit().scale(drag_magnitude);
-      let offset = Vec2D::from_angle(self.heading).scale(self.specs.hull_length * 0.5);
-      forces.push(Force::new(String::from("Stern Drag"), self.loc - offset, drag));
+    self.tethers.iter().filter_map(|tether| tether.force(kinematics)).for_each(|force| sink.add_force(force));
+  }
+
+  fn kinematics(&self) -> Kinematics {
+    Kinematics::new(self.loc, self.vel, self.rot_vel, self.heading)
+  }
+
+  fn controls(&self) -> &ShipControls {
+    &self.controls
+  }
+
+  fn set_controls(&mut self, controls: ShipControls) {
+    self.controls = controls.bounded(self.specs.sails().len(), self.specs.length(), self.specs.beam());
+  }
+
+  fn tethers(&self) -> &[Tether] {
+    &self.tethers
+  }
+
+  fn tethers_mut(&mut self) -> &mut Vec<Tether> {
+    &mut self.tethers
+  }
+
+  fn engine(&self) -> Option<&Engine> {
+    self.engine.as_ref()
+  }
+
+  /// Lines are made fast at the bow, or led to a bridle between the bows of several hulls
+  fn bow_offset(&self) -> Vec2D {
+    Vec2D::at_x(self.specs.length() * 0.5)
+  }
+
+  /// Lines are made fast at the stern, or led to a bridle between the sterns of several hulls
+  fn stern_offset(&self) -> Vec2D {
+    Vec2D::at_x(-self.specs.length() * 0.5)
+  }
+
+  /// From one side to the other, including anything stretched between several hulls
+  fn hull(&self) -> Rectangle {
+    Rectangle::new(self.loc, self.heading, self.specs.length(), self.specs.beam())
+  }
+
+  fn shape(&self) -> ShipShape {
+    S::shape(self)
+  }
+
+  fn to_json(&self) -> serde_json::Value {
+    serde_json::to_value(self).unwrap()
+  }
+
+  fn state(&self) -> ShipState {
+    ShipState {
+      kinematics: self.kinematics(),
+      sail_angles: self.sail_angles.clone(),
+      controls: self.controls.clone(),
+      tethers: self.tethers.clone(),
+      fuel: self.engine.as_ref().map(|engine| engine.fuel),
+      track_time: None,
     }
+  }
 
-    return forces;
+  fn restore(&mut self, state: &ShipState) {
+    self.set_kinematics(state.kinematics);
+    self.sail_angles = state.sail_angles.clone();
+    self.controls = state.controls.clone();
+    self.tethers = state.tethers.clone();
+    if let (Some(engine), Some(fuel)) = (&mut self.engine, state.fuel) {
+      engine.fuel = fuel;
+    }
+  }
+
+  fn clone_box(&self) -> Box<dyn Ship> {
+    Box::new(self.clone())
+  }
+
+  fn heel(&self, wind_angle: f64, wind_speed: f64) -> f64 {
+    let kinematics = self.kinematics();
+    // Each sail's lift and drag push sideways at a third of its height above the deck, where the center of a triangle is
+    let heeling_moment: f64 = (0..self.specs.sails().len()).filter_map(|sail_index| {
+      let force = sail_force(
+        kinematics, self.specs.sails(), sail_index, &self.controls,
+        self.sail_angles[sail_index], wind_angle, wind_speed
+      )?;
+      let height = self.effective_sail(sail_index).height / 3.0 + self.specs.deck_height();
+      // Forces are applied once a step, so per second they are this much larger
+      let to_starboard = -(force.lift + force.drag).rotate(-kinematics.heading).y / DELTA_TIME;
+      Some(to_starboard * height)
+    }).sum();
+    let crew_moment = self.controls.crew_position.y * CREW_MASS * GRAVITY;
+    let righting_moment = self.specs.righting_moment(self.total_mass());
+    return ((heeling_moment - crew_moment) / righting_moment).clamp(-1.0, 1.0).asin();
   }
 }
 
+/// A dinghy or launch with a single hull and keel
+pub type AdjustableShip = SailingShip<ShipSpecs>;
+
 
 #[tauri::command(rename_all = "snake_case")]
-pub fn debug_ship_physics(wind_angle: f64, wind_speed: f64, velocity: Vec2D, rot_velocity: f64, heading: f64, sails: Vec<f64>, rudder_angle: f64) -> PhysicsShapes {
+pub fn debug_ship_physics(wind_angle: f64, wind_speed: f64, velocity: Vec2D, rot_velocity: f64, heading: f64, controls: ShipControls, tethers: Vec<Tether>) -> PhysicsShapes {
   // Create the specified ship
+  let specs = ShipSpecs::default();
+  let controls = controls.bounded(specs.sails.len(), specs.hull_length, specs.hull_width);
   let mut ship = AdjustableShip::new(
-    ShipSpecs::default(),
+    specs,
     Vec2D::new(0.0,0.0),
     velocity,
     rot_velocity,
     heading,
-    sails,
-    rudder_angle
+    controls
   );
+  ship.tethers = tethers;
+  ship.trim_sails(wind_angle, wind_speed);
 
   // Calculate all forces acting on the ship
-  let mut forces = ship.forces(wind_angle, wind_speed);
+  let mut forces = ship.forces(wind_angle, wind_speed, Vec2D::zeros());
   let wind_source: Vec2D = Vec2D::new(0.0, 13.0);
   let wind_vec: Vec2D = Vec2D::from_angle(invert_angle(wind_angle)).scale(wind_speed);
   let rot_source: Vec2D = Vec2D::new(13.0, 0.0);
   let rot_vec: Vec2D = Vec2D::new(0.0, rot_velocity);
 
-  forces.insert(0, Force::new(String::from("Wind"), wind_source, wind_vec));
-  forces.insert(1, Force::new(String::from("Velocity"), wind_source, velocity));
-  forces.insert(2, Force::new(String::from("Apparent Wind"), wind_source, calculate_apparent_wind_simple(velocity, wind_angle, wind_speed)));
-  forces.insert(3, Force::new(String::from("Rotation"), rot_source, rot_vec));
+  forces.insert(0, Force::new(ForceName::Wind, wind_source, wind_vec));
+  forces.insert(1, Force::new(ForceName::Velocity, wind_source, velocity));
+  forces.insert(2, Force::new(ForceName::ApparentWind, wind_source, calculate_apparent_wind_simple(velocity, wind_angle, wind_speed)));
+  forces.insert(3, Force::new(ForceName::Rotation, rot_source, rot_vec));
 
   // Debug list of forces
   // forces.iter().for_each(|f| println!("{}", f.name));
@@ -320,12 +761,109 @@ pub fn debug_ship_physics(wind_angle: f64, wind_speed: f64, velocity: Vec2D, rot
     arrows.push(Arrow::from_force(force))
   );
   let shapes = PhysicsShapes {
-    ship: AdjustableShipShape::new(&ship),
+    ship: ship.shape(),
     forces: arrows
   };
 
   // Debug application of forces
-  ship.update(wind_angle, wind_speed);
+  ship.update(wind_angle, wind_speed, Vec2D::zeros(), &[]);
 
   return shapes;
 }
+
+
+#[cfg(test)]
+mod tests {
+  use std::f64::consts::PI;
+  use super::{sail_force, trim_sails, AdjustableShip, SailSpecs, Ship, ShipControls, ShipSpecs};
+  use crate::{geometry::Vec2D, physics::Kinematics};
+
+  /// A dinghy after two seconds on a reach, with the wind from the north
+  fn reaching(controls: ShipControls) -> AdjustableShip {
+    let mut ship = AdjustableShip::new(ShipSpecs::default(), Vec2D::zeros(), Vec2D::new(2.0, 0.0), 0.0, 0.0, ShipControls::new(vec![1.0], 0.0));
+    ship.set_controls(controls);
+    for _ in 0..60 {
+      ship.update(PI * 0.5, 5.0, Vec2D::zeros(), &[]);
+    }
+    return ship;
+  }
+
+  #[test]
+  fn test_traveller_vang_and_crew_change_sailing() {
+    let trimmed = ShipControls::new(vec![1.0], 0.0);
+    let baseline = reaching(trimmed.clone());
+
+    let mut traveller = trimmed.clone();
+    traveller.traveller_positions = vec![1.0];
+    let ship = reaching(traveller);
+    assert_ne!(ship.sail_angles[0], baseline.sail_angles[0]);
+    assert_ne!(ship.kinematics(), baseline.kinematics());
+
+    let mut vang = trimmed.clone();
+    vang.vang_tensions = vec![0.0];
+    assert_ne!(reaching(vang).kinematics(), baseline.kinematics());
+
+    // The crew's weight to port moves the center of mass with it
+    let mut crew = trimmed.clone();
+    crew.crew_position = Vec2D::new(0.0, 0.5);
+    let mut ship = AdjustableShip::new(ShipSpecs::default(), Vec2D::zeros(), Vec2D::zeros(), 0.0, 0.0, trimmed.clone());
+    ship.set_controls(crew.clone());
+    assert!(ship.center_of_mass().y > 0.0);
+    assert_ne!(reaching(crew).kinematics(), baseline.kinematics());
+  }
+
+  #[test]
+  fn test_reefing_takes_in_area() {
+    let sail = SailSpecs::new(0.0, 2.0, 5.0);
+    assert!((sail.reefed(0.5).area() - sail.area() * 0.5).abs() < 1e-9);
+    assert!((sail.reefed(0.25).area() - sail.area() * 0.75).abs() < 1e-9);
+    assert_eq!(sail.reefed(1.0).area(), 0.0);
+
+    // Close hauled, the force on the sail falls as more of it is reefed
+    let specs = ShipSpecs::default();
+    let kinematics = Kinematics::new(Vec2D::zeros(), Vec2D::new(2.0, 0.0), 0.0, 0.0);
+    let force = |reef: f64| {
+      let mut controls = ShipControls::new(vec![1.0], 0.0);
+      controls.reef_amounts = vec![reef];
+      let force = sail_force(kinematics, &specs.sails, 0, &controls, 0.0, PI / 3.0, 5.0).unwrap();
+      (force.lift + force.drag).magnitude()
+    };
+    let forces: Vec<f64> = [0.0, 0.3, 0.6, 0.9].into_iter().map(force).collect();
+    assert!(forces.windows(2).all(|pair| pair[1] < pair[0]), "{:?}", forces);
+  }
+
+  #[test]
+  fn test_reefed_sail_is_trimmed_by_its_own_boom() {
+    // A shorter boom swings further on the same length of sheet
+    let specs = ShipSpecs::default();
+    let kinematics = Kinematics::new(Vec2D::zeros(), Vec2D::new(2.0, 0.0), 0.0, 0.0);
+    let trimmed_angle = |reef: f64| {
+      let mut controls = ShipControls::new(vec![1.0], 0.0);
+      controls.reef_amounts = vec![reef];
+      let mut sail_angles = vec![0.0];
+      trim_sails(kinematics, &specs.sails, &controls, specs.hull_width, &mut sail_angles, PI * 0.5, 5.0);
+      sail_angles[0].abs()
+    };
+    assert!(trimmed_angle(0.5) > trimmed_angle(0.0));
+  }
+
+  #[test]
+  fn test_missing_sail_controls_are_filled_in() {
+    let mut controls = ShipControls::new(vec![], 0.0);
+    controls.vang_tensions = vec![0.5, 0.5];
+    let ship = reaching(controls);
+    assert_eq!(ship.controls().mainsheet_lengths.len(), 1);
+    assert_eq!(ship.controls().reef_amounts, vec![0.0]);
+    assert_eq!(ship.controls().traveller_positions, vec![0.0]);
+    assert_eq!(ship.controls().vang_tensions, vec![0.5]);
+  }
+
+  #[test]
+  fn test_no_heel_at_rest_in_calm() {
+    let ship = AdjustableShip::new(ShipSpecs::default(), Vec2D::zeros(), Vec2D::zeros(), 0.0, 0.0, ShipControls::new(vec![4.0], 0.0));
+    assert_eq!(ship.heel(1.5, 0.0), 0.0);
+    // Drifting along with the wind leaves no apparent wind either
+    let ship = AdjustableShip::new(ShipSpecs::default(), Vec2D::zeros(), Vec2D::new(5.0, 0.0), 0.0, 0.0, ShipControls::new(vec![4.0], 0.0));
+    assert_eq!(ship.heel(std::f64::consts::PI, 5.0), 0.0);
+  }
+}
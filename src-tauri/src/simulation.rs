@@ -1,119 +1,785 @@
-use std::{f64::consts::PI, sync::Mutex};
+use std::{collections::{BTreeMap, BTreeSet}, sync::Mutex};
 
 use rand::{rngs::StdRng, Rng, SeedableRng};
-use serde::Serialize;
-use tauri::State;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
 
-use crate::geometry::Vec2D;
-use crate::drawing::AdjustableShipShape;
-use crate::ship::{AdjustableShip, Ship, ShipSpecs};
+use crate::determinism::{Input, InputLog, LogCursor, StateHasher, CHECKPOINT_INTERVAL};
+use crate::geometry::{Rectangle, Vec2D};
+use crate::instruments::{Instruments, InstrumentsConfig, Readings};
+use crate::drawing::{rope, Polygon, ShipShape};
+use crate::events::{self, SimEvent};
+use crate::mooring::{check_line_length, Tether, TetherKind};
+use crate::physics::{Force, Kinematics};
+use crate::recording::Recorder;
+use crate::scenario::{Scenario, ShipPreset};
+use crate::scripting::{LoadedScripts, ScriptAction, ScriptError, Scripts, ShipView, WorldView};
+use crate::snapshots::Snapshots;
+use crate::spatial::{SpatialGrid, DEFAULT_CELL_SIZE};
+use crate::ship::{Ship, ShipControls, ShipState};
+use crate::towing::{ShipLine, DEFAULT_BREAKING_STRENGTH};
 
 pub const DELTA_TIME: f64 = 1.0 / 30.0; // seconds
+pub const PARALLEL_THRESHOLD: usize = 64; // Ships before updating them on several threads is worth the overhead
+pub const MARK_RADIUS: f64 = 5.0; // m, how close a ship has to come to a mark to have reached it
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct SimSettings {
-  wind_angle: f64,
-  wind_speed: f64,
+  pub wind_angle: f64,
+  pub wind_speed: f64,
+  /// Velocity of the water over the ground
+  #[serde(default = "Vec2D::zeros")]
+  pub current: Vec2D,
 }
 impl SimSettings {
   pub fn new(wind_angle: f64, wind_speed: f64) -> Self {
-    Self { wind_angle, wind_speed }
+    Self { wind_angle, wind_speed, current: Vec2D::zeros() }
+  }
+
+  pub fn with_current(mut self, current: Vec2D) -> Self {
+    self.current = current;
+    self
+  }
+}
+
+/// Where every ship was at one step of a batch run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrajectoryPoint {
+  step: u64,
+  ships: Vec<Kinematics>,
+}
+
+/// The outcome of advancing the simulation by many steps in one call
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchResult {
+  step: u64,
+  ships: Vec<serde_json::Value>,
+  trajectory: Vec<TrajectoryPoint>,
+}
+
+/// Everything that changes as the simulation runs, to put it back to later
+///
+/// The log is kept as how far it had got rather than a copy, so this stays the same size however long the run.
+#[derive(Clone)]
+pub struct SimState {
+  step: u64,
+  population: Vec<Box<dyn Ship>>,
+  lines: Vec<ShipLine>,
+  settings: SimSettings,
+  random: StdRng,
+  instruments: BTreeMap<usize, Instruments>,
+  marks: Vec<Vec2D>,
+  contacts: BTreeSet<(usize, usize)>,
+  course_progress: Vec<usize>,
+  scripts: LoadedScripts,
+  log: LogCursor,
+}
+impl SimState {
+  pub fn step(&self) -> u64 {
+    self.step
   }
 }
 
+#[derive(Clone)]
 pub struct Simulation {
+  /// What the simulation starts from, and goes back to when reset
+  scenario: Scenario,
   step: u64,
-  population: Vec<AdjustableShip>,
+  population: Vec<Box<dyn Ship>>,
+  /// Where every ship is, kept up to date as they move
+  grid: SpatialGrid,
+  lines: Vec<ShipLine>,
   settings: SimSettings,
   random: StdRng,
+  /// Instruments fitted to ships, by the index of the ship
+  instruments: BTreeMap<usize, Instruments>,
+  /// Course marks placed by scenarios, which ships don't collide with
+  marks: Vec<Vec2D>,
+  /// Pairs of ships whose hulls are touching, by index with the lower first
+  contacts: BTreeSet<(usize, usize)>,
+  /// The next mark each ship has to reach, by the index of the ship
+  course_progress: Vec<usize>,
+  scripts: Scripts,
+  /// Events that haven't been sent to the frontend yet
+  events: Vec<SimEvent>,
+  /// Ships before they are updated on several threads
+  parallel_threshold: usize,
+  /// Everything done to the simulation since it started, so the run can be reproduced
+  log: InputLog,
+  /// Buffers reused every step rather than allocated afresh
+  hulls: Vec<Rectangle>,
+  new_contacts: BTreeSet<(usize, usize)>,
+  line_forces: Vec<Vec<Force>>,
 }
 impl Simulation {
+  /// The sandbox ships, in the given wind
   pub fn new(seed: u64, wind_angle: f64, wind_speed: f64) -> Self {
-    Self {
+    return Self::from_scenario(Scenario::sandbox(seed, wind_angle, wind_speed)).unwrap();
+  }
+  pub fn from_scenario(scenario: Scenario) -> Result<Self, String> {
+    let settings = scenario.start_settings();
+    let mut sim = Self {
       step: 0,
-      population: Self::debug_ships(),
-      settings: SimSettings::new(wind_angle, wind_speed),
-      random: StdRng::seed_from_u64(seed)
-    }
-  }
-  fn debug_ships() -> Vec<AdjustableShip> {
-    let mut ships = Vec::new();
-    ships.push(AdjustableShip::new(
-      ShipSpecs::default(),
-      Vec2D::new(50.0, 25.0),
-      Vec2D::new(0.0, 0.0),
-      0.0,
-      PI,
-      vec![0.0],
-      0.0
-    ));
-    ships.push(AdjustableShip::new(
-      ShipSpecs::default(),
-      Vec2D::new(50.0, 50.0),
-      Vec2D::new(0.0, 0.0),
-      0.0,
-      PI,
-      vec![0.0],
-      0.0
-    ));
-    ships.push(AdjustableShip::new(
-      ShipSpecs::default(),
-      Vec2D::new(50.0, 75.0),
-      Vec2D::new(0.0, 0.0),
-      0.0,
-      PI,
-      vec![0.0],
-      0.0
-    ));
-    return ships;
+      population: Vec::new(),
+      grid: SpatialGrid::new(DEFAULT_CELL_SIZE),
+      lines: Vec::new(),
+      settings,
+      random: StdRng::seed_from_u64(scenario.seed),
+      instruments: BTreeMap::new(),
+      marks: Vec::new(),
+      contacts: BTreeSet::new(),
+      course_progress: Vec::new(),
+      scripts: Scripts::default(),
+      events: Vec::new(),
+      parallel_threshold: PARALLEL_THRESHOLD,
+      log: InputLog::new(&scenario),
+      hulls: Vec::new(),
+      new_contacts: BTreeSet::new(),
+      line_forces: Vec::new(),
+      scenario,
+    };
+    sim.restart()?;
+    return Result::Ok(sim);
   }
   pub fn step(&mut self) {
+    self.apply_scheduled_changes();
+    self.run_scripts();
     self.step += 1;
 
-    self.population.iter_mut().for_each(| ship |
-      ship.update(self.settings.wind_angle, self.settings.wind_speed)
-    );
+    // Lines pull with forces worked out before any ship moves, so each ship's update depends only on the ship itself,
+    // and comes out the same whichever thread it runs on
+    self.update_line_forces();
+    let line_forces = std::mem::take(&mut self.line_forces);
+    let settings = self.settings;
+    let update = |(ship, external_forces): (&mut Box<dyn Ship>, &Vec<Force>)|
+      ship.update(settings.wind_angle, settings.wind_speed, settings.current, external_forces);
+    if self.population.len() >= self.parallel_threshold {
+      self.population.par_iter_mut().zip(line_forces.par_iter()).for_each(update);
+    } else {
+      self.population.iter_mut().zip(line_forces.iter()).for_each(update);
+    }
+    self.line_forces = line_forces;
+    self.grid.update(&self.population);
+    self.detect_collisions();
+    self.track_course();
+    for (index, instruments) in self.instruments.iter_mut() {
+      if let Some(ship) = self.population.get(*index) {
+        instruments.update(ship.as_ref(), self.settings, &mut self.random);
+      }
+    }
+
+    if self.step % CHECKPOINT_INTERVAL == 0 {
+      let hash = self.state_hash();
+      self.log.checkpoint(self.step, hash);
+    }
+    if self.scenario.end_step() == Some(self.step) {
+      self.events.push(SimEvent::ScenarioFinished);
+    }
+  }
+  /// Change the wind and current when the scenario says they change
+  fn apply_scheduled_changes(&mut self) {
+    if let Some(shift) = self.scenario.wind_shift_at(self.step) {
+      self.set_settings(shift.angle, shift.speed);
+    }
+    if let Some(shift) = self.scenario.current_shift_at(self.step) {
+      self.set_current(shift.velocity);
+    }
   }
-  pub fn get_population(&self) -> &Vec<AdjustableShip> {
+  /// Report ships whose hulls have just come into contact
+  fn detect_collisions(&mut self) {
+    let contacts = &mut self.new_contacts;
+    let hulls = &mut self.hulls;
+    contacts.clear();
+    hulls.clear();
+    hulls.extend(self.population.iter().map(|ship| ship.hull()));
+    let population = &self.population;
+    for (a, hull) in hulls.iter().enumerate() {
+      if !population[a].solid() {
+        continue;
+      }
+      let radius = hull.radius();
+      self.grid.for_each_near(hull.center, radius + self.grid.max_radius(), |b| {
+        let other = &hulls[b];
+        // Most ships nearby are too far apart for their hulls to touch whichever way they point
+        if b > a && population[b].solid() && hull.center.dist(other.center) <= radius + other.radius() && hull.overlaps(other) {
+          contacts.insert((a, b));
+        }
+      });
+    }
+    for &(a, b) in contacts.difference(&self.contacts) {
+      self.events.push(SimEvent::Collision { a, b });
+    }
+    std::mem::swap(&mut self.contacts, contacts);
+  }
+  /// Move ships on to the next mark of the course as they reach each one
+  fn track_course(&mut self) {
+    if self.marks.is_empty() {
+      return;
+    }
+    self.course_progress.resize(self.population.len(), 0);
+    for (mark, loc) in self.marks.iter().enumerate() {
+      for ship in self.grid.near(*loc, MARK_RADIUS) {
+        if self.course_progress[ship] != mark {
+          continue;
+        }
+        self.course_progress[ship] += 1;
+        self.events.push(SimEvent::MarkReached { ship, mark });
+        if mark + 1 == self.marks.len() {
+          self.events.push(SimEvent::CourseFinished { ship });
+        }
+      }
+    }
+  }
+  /// Whether the scenario has run for as long as it lasts
+  pub fn finished(&self) -> bool {
+    matches!(self.scenario.end_step(), Some(end_step) if self.step >= end_step)
+  }
+  /// Hash of the exact state of the settings, every ship and line and the random numbers still to come,
+  /// which is the same whenever a run is reproduced
+  pub fn state_hash(&self) -> u64 {
+    let mut hasher = StateHasher::new();
+    hasher.write_u64(self.step);
+    hasher.write_f64(self.settings.wind_angle);
+    hasher.write_f64(self.settings.wind_speed);
+    hasher.write_vec(self.settings.current);
+    // The generator's state can't be read, but the next number it gives depends on all of it
+    hasher.write_u64(self.random.clone().gen());
+    for ship in &self.population {
+      let state = ship.state();
+      hasher.write_vec(state.kinematics.loc);
+      hasher.write_vec(state.kinematics.vel);
+      hasher.write_f64(state.kinematics.rot_vel);
+      hasher.write_f64(state.kinematics.heading);
+      state.sail_angles.iter().for_each(|angle| hasher.write_f64(*angle));
+      let controls = state.controls;
+      [&controls.mainsheet_lengths, &controls.reef_amounts, &controls.traveller_positions, &controls.vang_tensions]
+        .iter().for_each(|values| values.iter().for_each(|value| hasher.write_f64(*value)));
+      hasher.write_vec(controls.crew_position);
+      hasher.write_f64(controls.rudder_angle);
+      hasher.write_f64(controls.throttle);
+      for tether in &state.tethers {
+        hasher.write_vec(tether.anchor_point);
+        hasher.write_vec(tether.attachment);
+        hasher.write_f64(tether.length);
+        hasher.write_u64(tether.dragging as u64);
+      }
+      if let Some(fuel) = state.fuel {
+        hasher.write_f64(fuel);
+      }
+      if let Some(track_time) = state.track_time {
+        hasher.write_f64(track_time);
+      }
+    }
+    for line in &self.lines {
+      hasher.write_u64(line.from as u64);
+      hasher.write_u64(line.to as u64);
+      hasher.write_f64(line.length);
+    }
+    return hasher.finish();
+  }
+  pub fn get_log(&self) -> &InputLog {
+    &self.log
+  }
+  /// Give an input from a log, as if it had come from the frontend, failing if it can't be given
+  pub fn apply(&mut self, input: &Input) -> Result<(), String> {
+    match input.clone() {
+      Input::Controls { ship, controls } => self.update_ship_controls(ship, controls),
+      Input::Throttle { ship, throttle } => self.update_ship_throttle(ship, throttle),
+      Input::Settings(settings) => self.change_settings(settings),
+      Input::DropAnchor { ship, rode_length } => self.drop_anchor(ship, rode_length)?,
+      Input::WeighAnchor { ship } => self.weigh_anchor(ship),
+      Input::MoorShip { ship, mooring_point, line_length } => self.moor_ship(ship, mooring_point, line_length)?,
+      Input::CastOff { ship } => self.cast_off(ship),
+      // Only lines that were added successfully are logged, so this only fails for a log that has been changed
+      Input::AddLine(line) => self.add_line(line)?,
+      Input::ReleaseLines { ship } => self.release_lines(ship),
+      Input::Instruments { ship, config } => self.set_instruments(ship, config)?,
+      Input::AddShip { preset, loc, heading } => {
+        self.spawn_ship(preset, loc, heading);
+      }
+    }
+    return Result::Ok(());
+  }
+  /// Step the given number of times, recording every ship's kinematics every `record_interval` steps
+  pub fn run(&mut self, steps: u64, record_interval: Option<u64>, mut after_step: impl FnMut(&Simulation)) -> Vec<TrajectoryPoint> {
+    let mut trajectory = Vec::new();
+    for i in 1..=steps {
+      self.step();
+      after_step(self);
+      match record_interval {
+        Some(interval) if i % interval == 0 || i == steps => {
+          trajectory.push(TrajectoryPoint {
+            step: self.step,
+            ships: self.population.iter().map(|ship| ship.kinematics()).collect(),
+          });
+        }
+        _ => {}
+      }
+    }
+    return trajectory;
+  }
+  /// Run for a number of steps, like `run`, returning where the ships ended up
+  pub fn run_batch(&mut self, steps: u64, trajectory_interval: Option<u64>, after_step: impl FnMut(&Simulation)) -> BatchResult {
+    let trajectory = self.run(steps, trajectory_interval, after_step);
+    return BatchResult {
+      step: self.step,
+      ships: self.population.iter().map(|ship| ship.to_json()).collect(),
+      trajectory,
+    };
+  }
+  /// Calculate the pull of every line between ships, parting any line pulled past its breaking strength
+  fn update_line_forces(&mut self) {
+    let forces = &mut self.line_forces;
+    forces.iter_mut().for_each(|ship_forces| ship_forces.clear());
+    forces.resize(self.population.len(), Vec::new());
+    let population = &self.population;
+    let events = &mut self.events;
+    self.lines.retain(|line| {
+      let from = population[line.from].kinematics();
+      let to = population[line.to].kinematics();
+      if line.tension(from, to) > line.breaking_strength {
+        events.push(SimEvent::LineParted { from: line.from, to: line.to });
+        return false;
+      }
+      if let Some((from_force, to_force)) = line.forces(from, to) {
+        forces[line.from].push(from_force);
+        forces[line.to].push(to_force);
+      }
+      return true;
+    });
+  }
+  /// Every force on every ship as things are now, including the pull of lines to other ships
+  pub fn forces(&self) -> Vec<Vec<Force>> {
+    let settings = self.settings;
+    let mut forces: Vec<Vec<Force>> = self.population.iter()
+      .map(|ship| ship.forces(settings.wind_angle, settings.wind_speed, settings.current))
+      .collect();
+    for line in &self.lines {
+      if let Some((from_force, to_force)) = line.forces(self.population[line.from].kinematics(), self.population[line.to].kinematics()) {
+        forces[line.from].push(from_force);
+        forces[line.to].push(to_force);
+      }
+    }
+    return forces;
+  }
+  pub fn get_step(&self) -> u64 {
+    self.step
+  }
+  /// Update ships on several threads once there are this many, or never if it is `usize::MAX`
+  pub fn set_parallel_threshold(&mut self, ships: usize) {
+    self.parallel_threshold = ships;
+  }
+  pub fn get_scenario(&self) -> &Scenario {
+    &self.scenario
+  }
+  pub fn get_marks(&self) -> &Vec<Vec2D> {
+    &self.marks
+  }
+  pub fn get_scripts_mut(&mut self) -> &mut Scripts {
+    &mut self.scripts
+  }
+  /// Everything scripts can see of the simulation
+  pub fn world_view(&self) -> WorldView {
+    WorldView {
+      step: self.step,
+      wind: self.settings,
+      ships: self.population.iter().enumerate().map(|(index, ship)| ShipView {
+        index,
+        kinematics: ship.kinematics(),
+        controls: ship.controls().clone(),
+        readings: self.readings(index),
+      }).collect(),
+      marks: self.marks.clone(),
+    }
+  }
+  /// Run a scenario script, making the changes it asks for
+  pub fn load_scenario_script(&mut self, source: &str) -> Result<(), String> {
+    let world = self.world_view();
+    let actions = self.scripts.load_scenario(source, &world)?;
+    self.apply_script_actions(actions);
+    return Ok(());
+  }
+  /// Let the scenario and controllers act before a step, reporting any that fail
+  fn run_scripts(&mut self) {
+    if self.scripts.is_empty() {
+      return;
+    }
+    let world = self.world_view();
+    let (actions, errors) = self.scripts.before_step(&world);
+    self.apply_script_actions(actions);
+    self.events.extend(errors.into_iter().map(SimEvent::ScriptError));
+  }
+  fn apply_script_actions(&mut self, actions: Vec<ScriptAction>) {
+    for action in actions {
+      match action {
+        ScriptAction::SetWind { angle, speed } => self.set_settings(angle, speed),
+        ScriptAction::AddShip { preset, loc, heading } => {
+          self.spawn_ship(preset, loc, heading);
+        }
+        ScriptAction::PlaceMark(mark) => self.marks.push(mark),
+        ScriptAction::ClearMarks => {
+          self.marks.clear();
+          self.course_progress.clear();
+        }
+        ScriptAction::SetControls { ship, controls } => self.update_ship_controls(ship, controls),
+        ScriptAction::SetThrottle { ship, throttle } => self.update_ship_throttle(ship, throttle),
+        ScriptAction::DropAnchor { ship, rode_length } => {
+          // Scripts check the rode, and like other actions, dropping an anchor from a ship that doesn't exist does nothing
+          let _ = self.drop_anchor(ship, rode_length);
+        }
+        ScriptAction::WeighAnchor { ship } => self.weigh_anchor(ship),
+      }
+    }
+  }
+  /// Copy everything that changes as the simulation runs
+  pub fn save_state(&self) -> SimState {
+    return SimState {
+      step: self.step,
+      population: self.population.clone(),
+      lines: self.lines.clone(),
+      settings: self.settings,
+      random: self.random.clone(),
+      instruments: self.instruments.clone(),
+      marks: self.marks.clone(),
+      contacts: self.contacts.clone(),
+      course_progress: self.course_progress.clone(),
+      scripts: self.scripts.loaded(),
+      log: self.log.cursor(),
+    };
+  }
+  /// Put the simulation back to a saved state, with the scripts it had then, forgetting anything logged since, in the
+  /// same scenario
+  pub fn restore_state(&mut self, state: &SimState) {
+    self.step = state.step;
+    self.population = state.population.clone();
+    self.grid.rebuild(&self.population);
+    self.lines = state.lines.clone();
+    self.settings = state.settings;
+    self.random = state.random.clone();
+    self.instruments = state.instruments.clone();
+    self.marks = state.marks.clone();
+    self.contacts = state.contacts.clone();
+    self.course_progress = state.course_progress.clone();
+    self.scripts.restore(&state.scripts);
+    self.log.rewind(state.log);
+    // Events from before the state was saved have either been sent already or didn't happen
+    self.events.clear();
+  }
+  /// Take the events that have happened since this was last called
+  pub fn drain_events(&mut self) -> Vec<SimEvent> {
+    return std::mem::take(&mut self.events);
+  }
+  pub fn get_settings(&self) -> SimSettings {
+    self.settings
+  }
+  pub fn set_settings(&mut self, wind_angle: f64, wind_speed: f64) {
+    self.change_settings(SimSettings::new(wind_angle, wind_speed).with_current(self.settings.current));
+  }
+  pub fn set_current(&mut self, current: Vec2D) {
+    self.change_settings(self.settings.with_current(current));
+  }
+  fn change_settings(&mut self, settings: SimSettings) {
+    self.settings = settings;
+    self.log.record(self.step, Input::Settings(self.settings));
+  }
+  pub fn ship_states(&self) -> Vec<ShipState> {
+    return self.population.iter().map(|ship| ship.state()).collect();
+  }
+  /// Put every ship back into a recorded state, as it was at the given step, dropping any ship added since
+  pub fn restore_ship_states(&mut self, step: u64, states: &[ShipState]) -> Result<(), String> {
+    if states.len() > self.population.len() {
+      return Result::Err(format!("Recorded {} ships but only {} are sailing", states.len(), self.population.len()));
+    }
+    self.step = step;
+    self.population.truncate(states.len());
+    self.population.iter_mut().zip(states.iter()).for_each(|(ship, state)| ship.restore(state));
+    self.instruments.retain(|index, _| *index < states.len());
+    self.course_progress.truncate(states.len());
+    self.grid.update(&self.population);
+    self.log.interrupt();
+    return Result::Ok(());
+  }
+  /// Put the wind, current, lines and marks back as they were recorded
+  pub fn restore_surroundings(&mut self, settings: SimSettings, lines: &[ShipLine], marks: &[Vec2D]) {
+    self.settings = settings;
+    self.lines = lines.to_vec();
+    self.marks = marks.to_vec();
+    self.log.interrupt();
+  }
+  pub fn get_population(&self) -> &Vec<Box<dyn Ship>> {
     return &self.population;
   }
-  pub fn set_population(&mut self, population: Vec<AdjustableShip>) {
+  /// The drawable outline of every ship
+  pub fn shapes(&self) -> Vec<ShipShape> {
+    return self.population.iter().map(|ship| ship.shape()).collect();
+  }
+  /// Add a new ship of the given kind, at rest in the water, returning its index
+  pub fn spawn_ship(&mut self, preset: ShipPreset, loc: Vec2D, heading: f64) -> usize {
+    self.log.record(self.step, Input::AddShip { preset, loc, heading });
+    self.population.push(preset.build(loc, self.settings.current, heading));
+    self.grid.update(&self.population);
+    return self.population.len() - 1;
+  }
+  /// Add any other ship to the population, returning its index, which the log can't reproduce
+  pub fn add_ship(&mut self, ship: Box<dyn Ship>) -> usize {
+    self.population.push(ship);
+    self.grid.update(&self.population);
+    self.log.interrupt();
+    return self.population.len() - 1;
+  }
+  pub fn set_population(&mut self, population: Vec<Box<dyn Ship>>) {
     self.population = population;
+    self.grid.rebuild(&self.population);
+    self.lines.clear();
+    self.instruments.clear();
+    self.contacts.clear();
+    self.course_progress.clear();
+    self.log.interrupt();
+  }
+  pub fn get_ship(&self, index: usize) -> Option<&dyn Ship> {
+    self.population.get(index).map(|ship| ship.as_ref())
   }
-  pub fn get_ship(&self, index: usize) -> Option<&AdjustableShip> {
-    self.population.get(index)
+  /// The first ship whose hull the point lands on
+  pub fn ship_at(&self, point: Vec2D) -> Option<usize> {
+    return self.grid.near(point, self.grid.max_radius()).into_iter()
+      .find(|index| self.population[*index].contains(point));
   }
-  pub fn update_ship_controls(&mut self, index: usize, sails: Vec<f64>, rudder: f64) {
+  /// Every ship centered within the distance of the point, by index
+  pub fn ships_near(&self, point: Vec2D, distance: f64) -> Vec<usize> {
+    return self.grid.near(point, distance);
+  }
+  /// Fit instruments to a ship, replacing any it already has, or take them off
+  pub fn set_instruments(&mut self, index: usize, config: Option<InstrumentsConfig>) -> Result<(), String> {
+    if let Some(config) = config {
+      config.validate()?;
+    }
+    self.log.record(self.step, Input::Instruments { ship: index, config });
+    match config {
+      Some(config) => self.instruments.insert(index, Instruments::new(config)),
+      None => self.instruments.remove(&index),
+    };
+    return Result::Ok(());
+  }
+  /// What the ship's instruments display, if it has any
+  pub fn readings(&self, index: usize) -> Option<Readings> {
+    self.instruments.get(&index).map(|instruments| instruments.readings())
+  }
+  pub fn update_ship_controls(&mut self, index: usize, controls: ShipControls) {
+    self.log.record(self.step, Input::Controls { ship: index, controls: controls.clone() });
     match self.population.get_mut(index) {
       Some(ship) => {
-        ship.mainsheet_lengths = sails;
-        ship.rudder_angle = rudder;
+        ship.set_controls(controls);
       }
       None => {}
     }
   }
+  pub fn update_ship_throttle(&mut self, index: usize, throttle: f64) {
+    self.log.record(self.step, Input::Throttle { ship: index, throttle });
+    match self.population.get_mut(index) {
+      Some(ship) => {
+        let mut controls = ship.controls().clone();
+        controls.throttle = throttle;
+        ship.set_controls(controls);
+      }
+      None => {}
+    }
+  }
+  /// Drop an anchor from the bow of the ship, paying out the given length of rode
+  pub fn drop_anchor(&mut self, index: usize, rode_length: f64) -> Result<(), String> {
+    check_line_length(rode_length)?;
+    let ship = match self.population.get_mut(index) {
+      Some(ship) => ship,
+      None => return Result::Err(String::from("No such ship")),
+    };
+    self.log.record(self.step, Input::DropAnchor { ship: index, rode_length });
+    let bow = ship.bow_offset();
+    let anchor_point = ship.kinematics().point(bow);
+    ship.tethers_mut().push(Tether::anchor(anchor_point, bow, rode_length));
+    return Result::Ok(());
+  }
+  pub fn weigh_anchor(&mut self, index: usize) {
+    self.log.record(self.step, Input::WeighAnchor { ship: index });
+    match self.population.get_mut(index) {
+      Some(ship) => {
+        ship.tethers_mut().retain(|tether| tether.kind != TetherKind::Anchor);
+      }
+      None => {}
+    }
+  }
+  /// Tie the bow of the ship to a fixed point with a line of the given length
+  pub fn moor_ship(&mut self, index: usize, mooring_point: Vec2D, line_length: f64) -> Result<(), String> {
+    check_line_length(line_length)?;
+    if !mooring_point.x.is_finite() || !mooring_point.y.is_finite() {
+      return Result::Err(String::from("Mooring point must be somewhere on the map"));
+    }
+    let ship = match self.population.get_mut(index) {
+      Some(ship) => ship,
+      None => return Result::Err(String::from("No such ship")),
+    };
+    self.log.record(self.step, Input::MoorShip { ship: index, mooring_point, line_length });
+    let bow = ship.bow_offset();
+    ship.tethers_mut().push(Tether::mooring(mooring_point, bow, line_length));
+    return Result::Ok(());
+  }
+  pub fn cast_off(&mut self, index: usize) {
+    self.log.record(self.step, Input::CastOff { ship: index });
+    match self.population.get_mut(index) {
+      Some(ship) => {
+        ship.tethers_mut().retain(|tether| tether.kind != TetherKind::Mooring);
+      }
+      None => {}
+    }
+  }
+  pub fn get_lines(&self) -> &Vec<ShipLine> {
+    &self.lines
+  }
+  /// The shape of every line between ships, for drawing
+  pub fn line_shapes(&self) -> Vec<Polygon> {
+    return self.lines.iter().map(|line| {
+      let from = &self.population[line.from];
+      let to = &self.population[line.to];
+      rope(from.kinematics().point(line.from_attachment), to.kinematics().point(line.to_attachment))
+    }).collect();
+  }
+  pub fn add_line(&mut self, line: ShipLine) -> Result<(), String> {
+    if line.from == line.to {
+      return Result::Err(String::from("A ship cannot be tied to itself"));
+    }
+    check_line_length(line.length)?;
+    if line.breaking_strength.is_nan() || line.breaking_strength <= 0.0 {
+      return Result::Err(String::from("Breaking strength must be more than zero"));
+    }
+    if line.from >= self.population.len() || line.to >= self.population.len() {
+      return Result::Err(String::from("No such ship"));
+    }
+    self.log.record(self.step, Input::AddLine(line.clone()));
+    self.lines.push(line);
+    return Result::Ok(());
+  }
+  /// Tie the stern of the towing ship to the bow of the ship being towed
+  pub fn tow_ship(&mut self, tug: usize, tow: usize, line_length: f64) -> Result<(), String> {
+    let (tug_stern, tow_bow) = match (self.population.get(tug), self.population.get(tow)) {
+      (Some(tug_ship), Some(tow_ship)) => (tug_ship.stern_offset(), tow_ship.bow_offset()),
+      _ => return Result::Err(String::from("No such ship")),
+    };
+    return self.add_line(ShipLine::new(tug, tug_stern, tow, tow_bow, line_length, DEFAULT_BREAKING_STRENGTH));
+  }
+  /// Tie two ships together bow to bow and stern to stern, holding them where they are
+  pub fn raft_ships(&mut self, a: usize, b: usize) -> Result<(), String> {
+    let (ship_a, ship_b) = match (self.population.get(a), self.population.get(b)) {
+      (Some(ship_a), Some(ship_b)) => (ship_a, ship_b),
+      _ => return Result::Err(String::from("No such ship")),
+    };
+    let (kinematics_a, kinematics_b) = (ship_a.kinematics(), ship_b.kinematics());
+    let bow_line = ShipLine::new(
+      a, ship_a.bow_offset(), b, ship_b.bow_offset(),
+      kinematics_a.point(ship_a.bow_offset()).dist(kinematics_b.point(ship_b.bow_offset())),
+      DEFAULT_BREAKING_STRENGTH
+    );
+    let stern_line = ShipLine::new(
+      a, ship_a.stern_offset(), b, ship_b.stern_offset(),
+      kinematics_a.point(ship_a.stern_offset()).dist(kinematics_b.point(ship_b.stern_offset())),
+      DEFAULT_BREAKING_STRENGTH
+    );
+    self.add_line(bow_line)?;
+    return self.add_line(stern_line);
+  }
+  /// Let go of every line made fast to the given ship
+  pub fn release_lines(&mut self, index: usize) {
+    self.log.record(self.step, Input::ReleaseLines { ship: index });
+    self.lines.retain(|line| !line.connects(index));
+  }
+  /// Start the scenario again from the beginning
   pub fn reset(&mut self) {
-    self.population = Self::debug_ships();
-    self.step = 0;
+    if let Err(message) = self.restart() {
+      self.events.push(SimEvent::ScriptError(ScriptError { ship: None, message }));
+    }
     println!("reset sim");
   }
+  /// Replace the scenario and start it, failing if any of its scripts do
+  pub fn load_scenario(&mut self, scenario: Scenario) -> Result<(), String> {
+    scenario.validate()?;
+    self.scenario = scenario;
+    return self.restart();
+  }
+  fn restart(&mut self) -> Result<(), String> {
+    let scenario = self.scenario.clone();
+    let current = scenario.start_settings().current;
+    self.population = scenario.ships.iter().map(|ship| ship.preset.build(ship.loc, current, ship.heading)).collect();
+    self.grid.rebuild(&self.population);
+    self.lines.clear();
+    self.instruments.clear();
+    self.marks = scenario.course.clone();
+    self.contacts.clear();
+    self.course_progress.clear();
+    self.scripts.clear();
+    self.events.clear();
+    self.step = 0;
+    self.settings = scenario.start_settings();
+    self.random = StdRng::seed_from_u64(scenario.seed);
+    self.log = InputLog::new(&scenario);
+
+    for (index, ship) in scenario.ships.iter().enumerate() {
+      if let Some(source) = &ship.controller {
+        self.scripts.attach_controller(index, source).map_err(|e| format!("Controller for ship {}: {}", index, e))?;
+      }
+    }
+    if let Some(source) = &scenario.script {
+      self.load_scenario_script(source)?;
+    }
+    return Result::Ok(());
+  }
 }
 
 
 #[tauri::command]
-pub fn reset_simulation(sim: State<Mutex<Simulation>>) {
+pub fn reset_simulation(sim: State<Mutex<Simulation>>, recorder: State<Mutex<Recorder>>, snapshots: State<Mutex<Snapshots>>) {
   let mut sim = sim.lock().unwrap();
 
   sim.reset();
+  recorder.lock().unwrap().clear();
+  snapshots.lock().unwrap().clear();
 }
 
 #[tauri::command]
-pub fn step_simulation(sim: State<Mutex<Simulation>>) -> u64 {
-  let mut sim = sim.lock().unwrap();
+pub fn step_simulation(app: AppHandle) -> u64 {
+  return events::step_and_publish(&app, true);
+}
 
-  sim.step();
+/// Advance by a number of steps or a simulated duration in seconds, optionally returning where the ships went
+#[tauri::command(rename_all = "snake_case")]
+pub fn run_simulation_steps(
+  app: AppHandle,
+  steps: Option<u64>,
+  duration: Option<f64>,
+  trajectory_interval: Option<u64>
+) -> Result<BatchResult, String> {
+  let steps = match (steps, duration) {
+    (Some(steps), None) => steps,
+    (None, Some(duration)) if duration >= 0.0 && duration.is_finite() => (duration / DELTA_TIME).round() as u64,
+    (None, Some(_)) => return Result::Err(String::from("Duration must be zero or more seconds")),
+    _ => return Result::Err(String::from("Give either a number of steps or a duration")),
+  };
+  if trajectory_interval == Some(0) {
+    return Result::Err(String::from("Trajectory interval must be at least one step"));
+  }
 
-  return sim.step;
+  let result = {
+    let sim = app.state::<Mutex<Simulation>>();
+    let mut sim = sim.lock().unwrap();
+    let recorder = app.state::<Mutex<Recorder>>();
+    let mut recorder = recorder.lock().unwrap();
+    let snapshots = app.state::<Mutex<Snapshots>>();
+    let mut snapshots = snapshots.lock().unwrap();
+    recorder.stop_replay();
+    sim.run_batch(steps, trajectory_interval, |sim| {
+      recorder.record(sim);
+      snapshots.auto_snapshot(sim);
+    })
+  };
+  events::publish(&app);
+  return Result::Ok(result);
 }
 
 #[tauri::command(rename_all = "snake_case")]
@@ -122,43 +788,206 @@ pub fn get_sim_settings(sim: State<Mutex<Simulation>>) -> SimSettings {
   return sim.settings;
 }
 
+/// Change the wind, and the current if one is given
 #[tauri::command(rename_all = "snake_case")]
-pub fn set_sim_settings(sim: State<Mutex<Simulation>>, wind_angle: f64, wind_speed: f64) {
+pub fn set_sim_settings(sim: State<Mutex<Simulation>>, wind_angle: f64, wind_speed: f64, current: Option<Vec2D>) {
   let mut sim = sim.lock().unwrap();
-  sim.settings.wind_angle = wind_angle;
-  sim.settings.wind_speed = wind_speed;
+  sim.set_settings(wind_angle, wind_speed);
+  if let Some(current) = current {
+    sim.set_current(current);
+  }
 }
 
 #[tauri::command]
-pub fn get_population(sim: State<Mutex<Simulation>>) -> Vec<AdjustableShipShape> {
+pub fn get_population(sim: State<Mutex<Simulation>>) -> Vec<ShipShape> {
   let sim = sim.lock().unwrap();
-
-  let mut ships: Vec<AdjustableShipShape> = Vec::new();
-  sim.get_population().iter().for_each(|ship| ships.push(AdjustableShipShape::new(ship)));
-  return ships;
+  return sim.shapes();
 }
 
 #[tauri::command(rename_all = "snake_case")]
-pub fn get_ship(sim: State<Mutex<Simulation>>, index: usize) -> AdjustableShip {
+pub fn get_ship(sim: State<Mutex<Simulation>>, index: usize) -> serde_json::Value {
   let sim = sim.lock().unwrap();
   let ship = sim.get_ship(index).unwrap();
-  return ship.clone();
+  return ship.to_json();
 }
 
 #[tauri::command(rename_all = "snake_case")]
 pub fn get_ship_id(sim: State<Mutex<Simulation>>, loc: Vec2D) -> Option<usize> {
   let sim = sim.lock().unwrap();
-  for i in 0..sim.get_population().len() {
-    let ship = &sim.get_population()[i];
-    if ship.loc.dist(loc) < ship.specs.hull_length / 2.0 {
-      return Some(i);
-    }
-  }
-  return Option::None;
+  return sim.ship_at(loc);
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn set_ship_controls(sim: State<Mutex<Simulation>>, index: usize, controls: ShipControls) {
+  let mut sim = sim.lock().unwrap();
+  sim.update_ship_controls(index, controls);
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn set_ship_throttle(sim: State<Mutex<Simulation>>, index: usize, throttle: f64) {
+  let mut sim = sim.lock().unwrap();
+  sim.update_ship_throttle(index, throttle);
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn drop_anchor(sim: State<Mutex<Simulation>>, index: usize, rode_length: f64) -> Result<(), String> {
+  let mut sim = sim.lock().unwrap();
+  sim.drop_anchor(index, rode_length)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn weigh_anchor(sim: State<Mutex<Simulation>>, index: usize) {
+  let mut sim = sim.lock().unwrap();
+  sim.weigh_anchor(index);
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn moor_ship(sim: State<Mutex<Simulation>>, index: usize, mooring_point: Vec2D, line_length: f64) -> Result<(), String> {
+  let mut sim = sim.lock().unwrap();
+  sim.moor_ship(index, mooring_point, line_length)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn cast_off(sim: State<Mutex<Simulation>>, index: usize) {
+  let mut sim = sim.lock().unwrap();
+  sim.cast_off(index);
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_ship_lines(sim: State<Mutex<Simulation>>) -> Vec<ShipLine> {
+  let sim = sim.lock().unwrap();
+  return sim.get_lines().clone();
+}
+
+#[tauri::command]
+pub fn get_marks(sim: State<Mutex<Simulation>>) -> Vec<Vec2D> {
+  let sim = sim.lock().unwrap();
+  return sim.get_marks().clone();
+}
+
+#[tauri::command]
+pub fn get_ship_line_shapes(sim: State<Mutex<Simulation>>) -> Vec<Polygon> {
+  let sim = sim.lock().unwrap();
+  return sim.line_shapes();
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn add_ship_line(sim: State<Mutex<Simulation>>, line: ShipLine) -> Result<(), String> {
+  let mut sim = sim.lock().unwrap();
+  sim.add_line(line)
 }
 
 #[tauri::command(rename_all = "snake_case")]
-pub fn set_ship_controls(sim: State<Mutex<Simulation>>, index: usize, mainsheet_lengths: Vec<f64>, rudder_angle: f64) {
+pub fn tow_ship(sim: State<Mutex<Simulation>>, tug: usize, tow: usize, line_length: f64) -> Result<(), String> {
   let mut sim = sim.lock().unwrap();
-  sim.update_ship_controls(index, mainsheet_lengths, rudder_angle);
+  sim.tow_ship(tug, tow, line_length)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn raft_ships(sim: State<Mutex<Simulation>>, a: usize, b: usize) -> Result<(), String> {
+  let mut sim = sim.lock().unwrap();
+  sim.raft_ships(a, b)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn release_ship_lines(sim: State<Mutex<Simulation>>, index: usize) {
+  let mut sim = sim.lock().unwrap();
+  sim.release_lines(index);
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::f64::consts::PI;
+  use super::{Simulation, PARALLEL_THRESHOLD};
+  use crate::{
+    geometry::Vec2D,
+    scenario::{Scenario, ShipDefinition, ShipPreset},
+    ship::ShipControls
+  };
+
+  const SEED: u64 = 12345;
+
+  /// The sandbox after some steps under sail and power, with a ship at anchor
+  fn underway(steps: u64) -> Simulation {
+    let mut sim = Simulation::new(SEED, 0.0, 5.0);
+    sim.update_ship_controls(0, ShipControls::new(vec![4.0], 0.2));
+    sim.update_ship_throttle(4, 0.8);
+    sim.drop_anchor(1, 10.0).unwrap();
+    sim.run(steps, None, |_| {});
+    return sim;
+  }
+
+  /// A fleet big enough to be stepped in parallel, on the given number of threads, stepped in parallel once it has
+  /// as many ships as the threshold
+  fn fleet_run(threads: usize, parallel_threshold: usize) -> Simulation {
+    let mut scenario = Scenario::sandbox(SEED, PI / 2.0, 5.0);
+    scenario.ships = (0..PARALLEL_THRESHOLD * 2).map(|i| ShipDefinition {
+      preset: ShipPreset::Dinghy,
+      loc: Vec2D::new((i % 16) as f64 * 10.0, (i / 16) as f64 * 10.0),
+      heading: i as f64 * 0.1,
+      controller: None,
+    }).collect();
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+    return pool.install(|| {
+      let mut sim = Simulation::from_scenario(scenario).unwrap();
+      sim.set_parallel_threshold(parallel_threshold);
+      sim.tow_ship(0, 1, 12.0).unwrap();
+      sim.update_ship_controls(2, ShipControls::new(vec![4.0], 0.3));
+      sim.run(150, None, |_| {});
+      sim
+    });
+  }
+
+  #[test]
+  fn test_restored_state_runs_the_same() {
+    let mut sim = underway(150);
+    let snapshot = sim.save_state();
+    let inputs = sim.get_log().inputs.len();
+    sim.update_ship_controls(3, ShipControls::new(vec![6.0], -0.3));
+    sim.run(200, None, |_| {});
+    sim.restore_state(&snapshot);
+    assert_eq!(sim.get_step(), 150);
+    assert_eq!(sim.get_log().inputs.len(), inputs);
+
+    // Without the input given after the snapshot, it runs just as it would have without ever going back
+    sim.run(200, None, |_| {});
+    let mut straight_through = underway(150);
+    straight_through.run(200, None, |_| {});
+    assert_eq!(sim.state_hash(), straight_through.state_hash());
+    assert_eq!(sim.ship_states(), straight_through.ship_states());
+  }
+
+  #[test]
+  fn test_restoring_forgets_events_and_keeps_lines() {
+    let mut sim = Simulation::new(SEED, 0.0, 5.0);
+    sim.tow_ship(4, 2, 1000.0).unwrap();
+    sim.drop_anchor(1, 10.0).unwrap();
+    sim.run(30, None, |_| {});
+    let snapshot = sim.save_state();
+    sim.release_lines(4);
+    sim.cast_off(1);
+    sim.drain_events();
+    sim.restore_state(&snapshot);
+    assert!(sim.drain_events().is_empty());
+    assert_eq!(sim.get_lines().len(), 1);
+    assert_eq!(sim.get_ship(1).unwrap().tethers().len(), 1);
+  }
+
+  #[test]
+  fn test_parallel_same_state() {
+    let one_thread = fleet_run(1, PARALLEL_THRESHOLD);
+    let parallel = fleet_run(4, PARALLEL_THRESHOLD);
+    assert_eq!(one_thread.state_hash(), parallel.state_hash());
+    assert_eq!(one_thread.ship_states(), parallel.ship_states());
+  }
+
+  #[test]
+  fn test_serial_same_state_as_parallel() {
+    let serial = fleet_run(4, usize::MAX);
+    let parallel = fleet_run(4, PARALLEL_THRESHOLD);
+    assert_eq!(serial.state_hash(), parallel.state_hash());
+    assert_eq!(serial.ship_states(), parallel.ship_states());
+    assert_eq!(serial.get_log().checkpoints, parallel.get_log().checkpoints);
+  }
 }
@@ -0,0 +1,190 @@
+use std::{
+  collections::{BTreeMap, VecDeque},
+  sync::Mutex
+};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::{
+  events,
+  recording::Recorder,
+  simulation::{SimState, Simulation}
+};
+
+pub const AUTO_SNAPSHOT_INTERVAL: u64 = 150; // Steps between automatic snapshots, every five seconds
+pub const AUTO_SNAPSHOT_CAPACITY: usize = 60; // Automatic snapshots kept, five minutes of rewind
+
+/// A snapshot available to restore, for display in the frontend
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SnapshotInfo {
+  name: Option<String>,
+  step: u64,
+}
+
+/// Saved states of the simulation that it can be put back to
+pub struct Snapshots {
+  named: BTreeMap<String, SimState>,
+  automatic: VecDeque<SimState>,
+  auto_interval: u64,
+  auto_capacity: usize,
+}
+impl Snapshots {
+  pub fn new(auto_interval: u64, auto_capacity: usize) -> Self {
+    Self { named: BTreeMap::new(), automatic: VecDeque::new(), auto_interval, auto_capacity }
+  }
+
+  pub fn take(&mut self, name: String, sim: &Simulation) {
+    self.named.insert(name, sim.save_state());
+  }
+
+  pub fn get(&self, name: &str) -> Option<&SimState> {
+    self.named.get(name)
+  }
+
+  pub fn remove(&mut self, name: &str) {
+    self.named.remove(name);
+  }
+
+  /// Take an automatic snapshot if one is due, forgetting any taken after this step
+  pub fn auto_snapshot(&mut self, sim: &Simulation) {
+    while self.automatic.back().map_or(false, |snapshot| snapshot.step() >= sim.get_step()) {
+      self.automatic.pop_back();
+    }
+    if sim.get_step() % self.auto_interval != 0 {
+      return;
+    }
+    self.automatic.push_back(sim.save_state());
+    while self.automatic.len() > self.auto_capacity {
+      self.automatic.pop_front();
+    }
+  }
+
+  /// The latest automatic snapshot from before the given step
+  pub fn before(&self, step: u64) -> Option<&SimState> {
+    self.automatic.iter().rev().find(|snapshot| snapshot.step() < step)
+  }
+
+  pub fn clear(&mut self) {
+    self.named.clear();
+    self.automatic.clear();
+  }
+
+  pub fn list(&self) -> Vec<SnapshotInfo> {
+    let named = self.named.iter()
+      .map(|(name, snapshot)| SnapshotInfo { name: Some(name.clone()), step: snapshot.step() });
+    let automatic = self.automatic.iter()
+      .map(|snapshot| SnapshotInfo { name: None, step: snapshot.step() });
+    return named.chain(automatic).collect();
+  }
+}
+
+/// Put the simulation back to a snapshot and show it straight away
+fn restore(app: &AppHandle, find: impl for<'a> FnOnce(&'a Snapshots, &Simulation) -> Option<&'a SimState>) -> Result<u64, String> {
+  let step = {
+    let sim = app.state::<Mutex<Simulation>>();
+    let mut sim = sim.lock().unwrap();
+    let snapshots = app.state::<Mutex<Snapshots>>();
+    let snapshots = snapshots.lock().unwrap();
+    let snapshot = match find(&snapshots, &sim) {
+      Some(snapshot) => snapshot,
+      None => return Result::Err(String::from("No such snapshot")),
+    };
+    sim.restore_state(snapshot);
+    app.state::<Mutex<Recorder>>().lock().unwrap().stop_replay();
+    sim.get_step()
+  };
+  events::publish(app);
+  return Result::Ok(step);
+}
+
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn take_snapshot(sim: State<Mutex<Simulation>>, snapshots: State<Mutex<Snapshots>>, name: String) {
+  let sim = sim.lock().unwrap();
+  let mut snapshots = snapshots.lock().unwrap();
+  snapshots.take(name, &sim);
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn restore_snapshot(app: AppHandle, name: String) -> Result<u64, String> {
+  return restore(&app, |snapshots, _| snapshots.get(&name));
+}
+
+/// Go back to the last automatic snapshot, further back each time it is called in a row
+#[tauri::command(rename_all = "snake_case")]
+pub fn rewind_simulation(app: AppHandle) -> Result<u64, String> {
+  return restore(&app, |snapshots, sim| snapshots.before(sim.get_step()));
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn delete_snapshot(snapshots: State<Mutex<Snapshots>>, name: String) {
+  let mut snapshots = snapshots.lock().unwrap();
+  snapshots.remove(&name);
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn list_snapshots(snapshots: State<Mutex<Snapshots>>) -> Vec<SnapshotInfo> {
+  let snapshots = snapshots.lock().unwrap();
+  return snapshots.list();
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::{SnapshotInfo, Snapshots};
+  use crate::simulation::Simulation;
+
+  /// Step the simulation, taking automatic snapshots as the app does
+  fn run(sim: &mut Simulation, snapshots: &mut Snapshots, steps: u64) {
+    for _ in 0..steps {
+      sim.step();
+      snapshots.auto_snapshot(sim);
+    }
+  }
+
+  #[test]
+  fn test_automatic_snapshots_keep_the_latest() {
+    let mut sim = Simulation::new(1, 0.0, 5.0);
+    let mut snapshots = Snapshots::new(10, 3);
+    run(&mut sim, &mut snapshots, 55);
+    let steps: Vec<u64> = snapshots.list().iter().map(|info| info.step).collect();
+    assert_eq!(steps, vec![30, 40, 50]);
+    assert_eq!(snapshots.before(50).unwrap().step(), 40);
+    assert_eq!(snapshots.before(51).unwrap().step(), 50);
+    assert!(snapshots.before(30).is_none());
+  }
+
+  #[test]
+  fn test_rewinding_forgets_later_snapshots() {
+    let mut sim = Simulation::new(1, 0.0, 5.0);
+    let mut snapshots = Snapshots::new(10, 10);
+    run(&mut sim, &mut snapshots, 45);
+    let earlier = snapshots.before(25).unwrap().clone();
+    sim.restore_state(&earlier);
+    assert_eq!(sim.get_step(), 20);
+
+    // Snapshots from the future that was left behind are dropped as the run carries on from the past
+    run(&mut sim, &mut snapshots, 5);
+    let steps: Vec<u64> = snapshots.list().iter().map(|info| info.step).collect();
+    assert_eq!(steps, vec![10, 20]);
+  }
+
+  #[test]
+  fn test_named_snapshots() {
+    let mut sim = Simulation::new(1, 0.0, 5.0);
+    let mut snapshots = Snapshots::new(10, 10);
+    run(&mut sim, &mut snapshots, 12);
+    snapshots.take(String::from("start"), &sim);
+    run(&mut sim, &mut snapshots, 30);
+    snapshots.take(String::from("start"), &sim);
+    assert_eq!(snapshots.get("start").unwrap().step(), 42);
+    assert_eq!(snapshots.list()[0], SnapshotInfo { name: Some(String::from("start")), step: 42 });
+
+    snapshots.remove("start");
+    assert!(snapshots.get("start").is_none());
+    snapshots.take(String::from("end"), &sim);
+    snapshots.clear();
+    assert!(snapshots.list().is_empty());
+  }
+}
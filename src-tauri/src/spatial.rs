@@ -0,0 +1,191 @@
+use std::{
+  collections::HashMap,
+  hash::{BuildHasherDefault, Hasher}
+};
+
+use crate::{geometry::Vec2D, ship::Ship};
+
+pub const DEFAULT_CELL_SIZE: f64 = 10.0; // m, a couple of boat lengths
+
+type Cell = (i64, i64);
+
+/// A quick hash for cell coordinates, which the standard library's hasher spends most of a search on
+#[derive(Debug, Default)]
+struct CellHasher {
+  hash: u64,
+}
+impl Hasher for CellHasher {
+  fn write(&mut self, bytes: &[u8]) {
+    bytes.iter().for_each(|byte| self.write_u64(*byte as u64));
+  }
+
+  fn write_i64(&mut self, value: i64) {
+    self.write_u64(value as u64);
+  }
+
+  fn write_u64(&mut self, value: u64) {
+    // FxHash's mixing, as used in the Rust compiler
+    self.hash = (self.hash.rotate_left(5) ^ value).wrapping_mul(0x517cc1b727220a95);
+  }
+
+  fn finish(&self) -> u64 {
+    self.hash
+  }
+}
+
+/// A uniform grid over the water, bucketing ships by the cell their center is in, for finding the ships near a point
+/// without looking at every ship
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpatialGrid {
+  cell_size: f64,
+  cells: HashMap<Cell, Vec<usize>, BuildHasherDefault<CellHasher>>,
+  /// Where each ship was when last indexed, and its cell
+  locs: Vec<(Vec2D, Cell)>,
+  /// The largest radius of any ship, so searches can reach ships centered outside the area searched
+  max_radius: f64,
+}
+impl SpatialGrid {
+  pub fn new(cell_size: f64) -> Self {
+    Self { cell_size, cells: HashMap::default(), locs: Vec::new(), max_radius: 0.0 }
+  }
+
+  pub fn max_radius(&self) -> f64 {
+    self.max_radius
+  }
+
+  fn cell(&self, loc: Vec2D) -> Cell {
+    ((loc.x / self.cell_size).floor() as i64, (loc.y / self.cell_size).floor() as i64)
+  }
+
+  /// Index the whole population again, after ships have been added or replaced
+  pub fn rebuild(&mut self, population: &[Box<dyn Ship>]) {
+    self.cells.clear();
+    self.locs.clear();
+    self.max_radius = 0.0;
+    for (index, ship) in population.iter().enumerate() {
+      let loc = ship.kinematics().loc;
+      let cell = self.cell(loc);
+      self.cells.entry(cell).or_default().push(index);
+      self.locs.push((loc, cell));
+      self.max_radius = f64::max(self.max_radius, ship.radius());
+    }
+  }
+
+  /// Move ships that have sailed into another cell, and index any added to the end of the population
+  pub fn update(&mut self, population: &[Box<dyn Ship>]) {
+    if population.len() < self.locs.len() {
+      self.rebuild(population);
+      return;
+    }
+    for (index, ship) in population.iter().enumerate() {
+      let loc = ship.kinematics().loc;
+      let cell = self.cell(loc);
+      if index == self.locs.len() {
+        self.cells.entry(cell).or_default().push(index);
+        self.locs.push((loc, cell));
+        self.max_radius = f64::max(self.max_radius, ship.radius());
+        continue;
+      }
+      let previous = self.locs[index].1;
+      self.locs[index] = (loc, cell);
+      if cell == previous {
+        continue;
+      }
+      if let Some(ships) = self.cells.get_mut(&previous) {
+        ships.retain(|ship| *ship != index);
+        if ships.is_empty() {
+          self.cells.remove(&previous);
+        }
+      }
+      self.cells.entry(cell).or_default().push(index);
+    }
+  }
+
+  /// Every ship centered within the distance of the point, by index
+  pub fn near(&self, point: Vec2D, distance: f64) -> Vec<usize> {
+    let mut ships = Vec::new();
+    self.for_each_near(point, distance, |index| ships.push(index));
+    ships.sort_unstable();
+    return ships;
+  }
+
+  /// Call the function with every ship centered within the distance of the point, in no particular order
+  pub fn for_each_near(&self, point: Vec2D, distance: f64, mut f: impl FnMut(usize)) {
+    if distance.is_nan() {
+      return;
+    }
+    let mut visit = |ships: &Vec<usize>| ships.iter()
+      .filter(|index| self.locs[**index].0.dist(point) <= distance)
+      .for_each(|index| f(*index));
+    // Counted as a float, as cells far enough away to be worth counting don't fit in an integer
+    let across = (2.0 * distance / self.cell_size).ceil() + 1.0;
+    match across * across <= self.cells.len() as f64 {
+      true => {
+        let (min_x, min_y) = self.cell(point - Vec2D::new(distance, distance));
+        let (max_x, max_y) = self.cell(point + Vec2D::new(distance, distance));
+        for x in min_x..=max_x {
+          for y in min_y..=max_y {
+            if let Some(ships) = self.cells.get(&(x, y)) {
+              visit(ships);
+            }
+          }
+        }
+      }
+      // Searching further than the ships are spread, so it's quicker to check every occupied cell
+      false => self.cells.values().for_each(visit),
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::{SpatialGrid, DEFAULT_CELL_SIZE};
+  use crate::{geometry::Vec2D, simulation::Simulation};
+
+  #[test]
+  fn test_near_matches_scan() {
+    let mut sim = Simulation::new(12345, 1.5, 5.0);
+    for _ in 0..300 {
+      sim.step();
+    }
+    let mut grid = SpatialGrid::new(DEFAULT_CELL_SIZE);
+    grid.rebuild(sim.get_population());
+    let points = [Vec2D::zeros(), Vec2D::new(50.0, 50.0), Vec2D::new(38.5, 71.0)];
+    for point in points {
+      for distance in [0.0, 5.0, 30.0, 1e6, f64::MAX, f64::INFINITY] {
+        let scanned: Vec<usize> = sim.get_population().iter().enumerate()
+          .filter(|(_, ship)| ship.kinematics().loc.dist(point) <= distance)
+          .map(|(index, _)| index)
+          .collect();
+        assert_eq!(grid.near(point, distance), scanned);
+        assert_eq!(sim.ships_near(point, distance), scanned);
+      }
+      assert!(grid.near(point, f64::NAN).is_empty());
+    }
+  }
+
+  #[test]
+  fn test_picks_hull_rectangle() {
+    let sim = Simulation::new(12345, 1.5, 5.0);
+    let ship = sim.get_ship(0).unwrap();
+    let kinematics = ship.kinematics();
+    assert_eq!(sim.ship_at(kinematics.loc), Some(0));
+    // Abeam of the center, inside the circle around the ship but outside its hull
+    let abeam = kinematics.loc + Vec2D::from_angle(kinematics.heading).rotate(std::f64::consts::PI * 0.5).scale(ship.radius() * 0.9);
+    assert!(!ship.contains(abeam));
+    assert_ne!(sim.ship_at(abeam), Some(0));
+  }
+
+  #[test]
+  fn test_picks_catamaran_trampoline() {
+    let sim = Simulation::new(12345, 1.5, 5.0);
+    // The sandbox's catamaran
+    let index = 3;
+    let kinematics = sim.get_ship(index).unwrap().kinematics();
+    // Between the hulls, where there is only the trampoline
+    assert_eq!(sim.ship_at(kinematics.loc), Some(index));
+    let forward = Vec2D::from_angle(kinematics.heading).scale(1.0);
+    assert_eq!(sim.ship_at(kinematics.loc + forward), Some(index));
+  }
+}
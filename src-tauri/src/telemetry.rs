@@ -0,0 +1,364 @@
+use std::{
+  collections::HashMap,
+  fs,
+  io::{self, Write},
+  sync::Mutex
+};
+
+use serde::Deserialize;
+use tauri::State;
+
+use crate::{
+  determinism,
+  physics::calculate_apparent_wind_simple,
+  recording::{Frame, Recorder},
+  scenario::Scenario,
+  simulation::{Simulation, DELTA_TIME}
+};
+
+const COLUMNAR_MAGIC: &[u8; 8] = b"SAILCOL1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum ExportFormat {
+  Csv,
+  Columnar,
+}
+
+/// Groups of columns that can be chosen for export
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Field {
+  Position,
+  Velocity,
+  Heading,
+  /// Radians the ship leans to starboard
+  Heel,
+  Controls,
+  ApparentWind,
+  /// Magnitude of every force acting on the ship, by name, when forces were recorded
+  Forces,
+}
+impl Field {
+  pub fn parse(name: &str) -> Result<Self, String> {
+    match name {
+      "position" => Ok(Field::Position),
+      "velocity" => Ok(Field::Velocity),
+      "heading" => Ok(Field::Heading),
+      "heel" => Ok(Field::Heel),
+      "controls" => Ok(Field::Controls),
+      "apparent_wind" => Ok(Field::ApparentWind),
+      "forces" => Ok(Field::Forces),
+      _ => Err(format!("Unknown field: {}", name)),
+    }
+  }
+}
+
+/// Which part of the recording to export, everything being exported when left out
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct ExportOptions {
+  pub ships: Option<Vec<usize>>,
+  pub fields: Option<Vec<Field>>,
+  pub from_step: Option<u64>,
+  pub to_step: Option<u64>,
+}
+impl ExportOptions {
+  fn includes_ship(&self, index: usize) -> bool {
+    self.ships.as_ref().map_or(true, |ships| ships.contains(&index))
+  }
+
+  fn includes_field(&self, field: Field) -> bool {
+    self.fields.as_ref().map_or(true, |fields| fields.contains(&field))
+  }
+
+  fn includes_step(&self, step: u64) -> bool {
+    self.from_step.map_or(true, |from| step >= from) && self.to_step.map_or(true, |to| step <= to)
+  }
+}
+
+/// Named columns of equal length, one row per ship per recorded step
+///
+/// Values that don't apply to a ship, such as the controls of a sail it doesn't have, are NaN.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Table {
+  names: Vec<String>,
+  columns: Vec<Vec<f64>>,
+  rows: usize,
+}
+impl Table {
+  /// Add a row given as name/value pairs, adding any columns not seen before
+  fn push_row(&mut self, values: Vec<(String, f64)>, column_index: &mut HashMap<String, usize>) {
+    for (name, value) in values {
+      let index = *column_index.entry(name.clone()).or_insert_with(|| {
+        self.names.push(name);
+        self.columns.push(vec![f64::NAN; self.rows]);
+        self.columns.len() - 1
+      });
+      self.columns[index].push(value);
+    }
+    self.rows += 1;
+    // Columns this row had no value for
+    self.columns.iter_mut().for_each(|column| column.resize(self.rows, f64::NAN));
+  }
+
+  pub fn write_csv(&self, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "{}", self.names.join(","))?;
+    for row in 0..self.rows {
+      let cells: Vec<String> = self.columns.iter().map(|column| match column[row].is_nan() {
+        true => String::new(),
+        false => column[row].to_string(),
+      }).collect();
+      writeln!(out, "{}", cells.join(","))?;
+    }
+    return Ok(());
+  }
+
+  /// Write as little-endian binary: the magic "SAILCOL1", the column count as u32 and row count as u64,
+  /// each column name as a u16 length then UTF-8, and finally each column's values as f64 one column after another
+  pub fn write_columnar(&self, out: &mut impl Write) -> io::Result<()> {
+    out.write_all(COLUMNAR_MAGIC)?;
+    out.write_all(&(self.columns.len() as u32).to_le_bytes())?;
+    out.write_all(&(self.rows as u64).to_le_bytes())?;
+    for name in &self.names {
+      out.write_all(&(name.len() as u16).to_le_bytes())?;
+      out.write_all(name.as_bytes())?;
+    }
+    for column in &self.columns {
+      for value in column {
+        out.write_all(&value.to_le_bytes())?;
+      }
+    }
+    return Ok(());
+  }
+}
+
+/// Build a table of the recorded frames
+pub fn build_table<'a>(frames: impl Iterator<Item = &'a Frame>, options: &ExportOptions) -> Table {
+  let mut table = Table::default();
+  let mut column_index = HashMap::new();
+  for frame in frames.filter(|frame| options.includes_step(frame.step)) {
+    for (index, state) in frame.ships.iter().enumerate() {
+      if !options.includes_ship(index) {
+        continue;
+      }
+      let mut row = vec![
+        (String::from("step"), frame.step as f64),
+        (String::from("time"), frame.step as f64 * DELTA_TIME),
+        (String::from("ship"), index as f64),
+      ];
+      let kinematics = state.kinematics;
+      if options.includes_field(Field::Position) {
+        row.push((String::from("x"), kinematics.loc.x));
+        row.push((String::from("y"), kinematics.loc.y));
+      }
+      if options.includes_field(Field::Velocity) {
+        row.push((String::from("vel_x"), kinematics.vel.x));
+        row.push((String::from("vel_y"), kinematics.vel.y));
+        row.push((String::from("rot_vel"), kinematics.rot_vel));
+      }
+      if options.includes_field(Field::Heading) {
+        row.push((String::from("heading"), kinematics.heading));
+      }
+      if options.includes_field(Field::Heel) {
+        row.push((String::from("heel"), frame.heel.get(index).copied().unwrap_or(f64::NAN)));
+      }
+      if options.includes_field(Field::Controls) {
+        let controls = &state.controls;
+        row.push((String::from("rudder_angle"), controls.rudder_angle));
+        row.push((String::from("throttle"), controls.throttle));
+        row.push((String::from("crew_x"), controls.crew_position.x));
+        row.push((String::from("crew_y"), controls.crew_position.y));
+        for (sail_index, sail_angle) in state.sail_angles.iter().enumerate() {
+          row.push((format!("sail_{}_angle", sail_index), *sail_angle));
+          row.push((format!("sail_{}_mainsheet", sail_index), controls.mainsheet_lengths.get(sail_index).copied().unwrap_or(f64::NAN)));
+          row.push((format!("sail_{}_reef", sail_index), controls.reef_amount(sail_index)));
+          row.push((format!("sail_{}_traveller", sail_index), controls.traveller_position(sail_index)));
+          row.push((format!("sail_{}_vang", sail_index), controls.vang_tension(sail_index)));
+        }
+      }
+      if options.includes_field(Field::ApparentWind) {
+        let apparent_wind = calculate_apparent_wind_simple(kinematics.vel, frame.settings.wind_angle, frame.settings.wind_speed);
+        row.push((String::from("apparent_wind_angle"), apparent_wind.to_angle()));
+        row.push((String::from("apparent_wind_speed"), apparent_wind.magnitude()));
+      }
+      if options.includes_field(Field::Forces) {
+        if let Some(forces) = frame.forces.get(index) {
+          row.extend(forces.iter().map(|(name, magnitude)| {
+            (format!("force_{}", name.to_string().to_lowercase().replace(' ', "_")), *magnitude)
+          }));
+        }
+      }
+      table.push_row(row, &mut column_index);
+    }
+  }
+  return table;
+}
+
+/// Write the recorded frames to a file in the given format, returning the number of rows written
+pub fn export<'a>(
+  frames: impl Iterator<Item = &'a Frame>,
+  options: &ExportOptions,
+  format: ExportFormat,
+  path: &str
+) -> Result<usize, String> {
+  let table = build_table(frames, options);
+  let mut out = io::BufWriter::new(fs::File::create(path).map_err(|e| e.to_string())?);
+  match format {
+    ExportFormat::Csv => table.write_csv(&mut out),
+    ExportFormat::Columnar => table.write_columnar(&mut out),
+  }.map_err(|e| e.to_string())?;
+  out.flush().map_err(|e| e.to_string())?;
+  return Ok(table.rows);
+}
+
+/// Run a simulation without the interface and export what happened, for `--export` on the command line
+///
+/// Arguments, after the program's name, are `--export <path> [--scenario <id or path> [--seed <n>] | --log <path>]
+/// [--format csv|columnar] [--steps <n>] [--ships 0,1] [--fields position,forces] [--from <step>] [--to <step>]`.
+/// The sandbox is run if neither a scenario nor a log is given.
+pub fn export_from_args(args: &[String]) -> Result<String, String> {
+  let mut path = None;
+  let mut scenario = None;
+  let mut seed = None;
+  let mut log = None;
+  let mut format = ExportFormat::Csv;
+  let mut steps = None;
+  let mut options = ExportOptions::default();
+
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+    match arg.as_str() {
+      "--export" => path = Some(value()?.clone()),
+      "--scenario" => scenario = Some(value()?.clone()),
+      "--seed" => seed = Some(value()?.parse::<u64>().map_err(|e| e.to_string())?),
+      "--log" => log = Some(value()?.clone()),
+      "--format" => format = match value()?.as_str() {
+        "csv" => ExportFormat::Csv,
+        "columnar" => ExportFormat::Columnar,
+        other => return Err(format!("Unknown format: {}", other)),
+      },
+      "--steps" => steps = Some(value()?.parse::<u64>().map_err(|e| e.to_string())?),
+      "--ships" => options.ships = Some(
+        value()?.split(',').map(|ship| ship.parse::<usize>().map_err(|e| e.to_string())).collect::<Result<_, _>>()?
+      ),
+      "--fields" => options.fields = Some(value()?.split(',').map(Field::parse).collect::<Result<_, _>>()?),
+      "--from" => options.from_step = Some(value()?.parse::<u64>().map_err(|e| e.to_string())?),
+      "--to" => options.to_step = Some(value()?.parse::<u64>().map_err(|e| e.to_string())?),
+      other => return Err(format!("Unknown argument: {}", other)),
+    }
+  }
+  let path = path.ok_or(String::from("No export path given"))?;
+  let steps = steps.or(options.to_step);
+  let with_forces = options.includes_field(Field::Forces);
+
+  let mut frames = Vec::new();
+  match log {
+    Some(_) if scenario.is_some() || seed.is_some() => {
+      return Err(String::from("A log already has its scenario and seed"));
+    }
+    Some(log) => {
+      // Replay the whole log unless told to stop sooner
+      determinism::replay(&determinism::load_log(&log)?, |sim| {
+        frames.push(Frame::capture(sim, with_forces));
+        return steps.map_or(true, |steps| sim.get_step() < steps);
+      })?;
+    }
+    None => {
+      // A scenario is either one of the bundled ones or a file
+      let mut scenario = match scenario {
+        Some(scenario) => match fs::read_to_string(&scenario) {
+          Ok(json) => Scenario::from_json(&json)?,
+          Err(_) => Scenario::bundled(&scenario)?,
+        },
+        None => Scenario::bundled("sandbox")?,
+      };
+      scenario.seed = seed.unwrap_or(scenario.seed);
+      // Run for as long as asked, or until the scenario ends, or ten minutes
+      let steps = steps.or(scenario.end_step()).unwrap_or((600.0 / DELTA_TIME) as u64);
+      let mut sim = Simulation::from_scenario(scenario)?;
+      sim.run(steps, None, |sim| frames.push(Frame::capture(sim, with_forces)));
+    }
+  }
+  let rows = export(frames.iter(), &options, format, &path)?;
+  return Ok(format!("Exported {} rows to {}", rows, path));
+}
+
+
+#[tauri::command(rename_all = "snake_case")]
+pub fn export_telemetry(
+  recorder: State<Mutex<Recorder>>,
+  path: String,
+  format: ExportFormat,
+  options: ExportOptions
+) -> Result<usize, String> {
+  let recorder = recorder.lock().unwrap();
+  return export(recorder.get_frames().iter(), &options, format, &path);
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::{build_table, export_from_args, ExportOptions, Field};
+  use crate::{
+    geometry::Vec2D,
+    recording::Frame,
+    ship::ShipControls,
+    simulation::Simulation,
+    towing::{ShipLine, DEFAULT_BREAKING_STRENGTH}
+  };
+
+  fn args(args: &[&str]) -> Vec<String> {
+    return args.iter().map(|arg| arg.to_string()).collect();
+  }
+
+  #[test]
+  fn test_rejects_unknown_arguments() {
+    let path = std::env::temp_dir().join("sail_unknown_argument.csv");
+    let path = path.to_str().unwrap();
+    assert_eq!(export_from_args(&args(&["--export", path, "--ship", "0"])), Err(String::from("Unknown argument: --ship")));
+    assert!(export_from_args(&args(&["--export", path, "--log", "run.json", "--seed", "3"])).is_err());
+    assert!(export_from_args(&args(&["--export", path, "--scenario", "nowhere"])).is_err());
+  }
+
+  #[test]
+  fn test_exports_scenario_with_seed() {
+    let path = std::env::temp_dir().join("sail_export_scenario.csv");
+    let path = path.to_str().unwrap();
+    let message = export_from_args(&args(&["--export", path, "--scenario", "wind_shift", "--seed", "7", "--steps", "30"]));
+    assert_eq!(message, Ok(format!("Exported 30 rows to {}", path)));
+    let csv = std::fs::read_to_string(path).unwrap();
+    assert_eq!(csv.lines().count(), 31);
+    assert!(csv.lines().next().unwrap().contains("force_"));
+  }
+
+  #[test]
+  fn test_forces_are_recorded_when_captured() {
+    // A line between two dinghies pulled just taut
+    let mut sim = Simulation::new(12345, 0.0, 5.0);
+    let (from, to) = (sim.get_population()[1].kinematics(), sim.get_population()[2].kinematics());
+    let length = from.loc.dist(to.loc) - 0.1;
+    sim.add_line(ShipLine::new(1, Vec2D::zeros(), 2, Vec2D::zeros(), length, DEFAULT_BREAKING_STRENGTH)).unwrap();
+    let options = ExportOptions { ships: Some(vec![1]), fields: Some(vec![Field::Forces]), ..ExportOptions::default() };
+
+    // Lines to other ships pull on the ship as much as anything it does itself
+    let table = build_table([Frame::capture(&sim, true)].iter(), &options);
+    assert!(table.names.iter().any(|name| name == "force_ship_line"));
+    assert!(table.names.iter().any(|name| name == "force_sail_0_lift"));
+
+    // Without forces recorded there is nothing to make them up from
+    let table = build_table([Frame::capture(&sim, false)].iter(), &options);
+    assert!(!table.names.iter().any(|name| name.starts_with("force_")));
+  }
+
+  #[test]
+  fn test_heel_is_exported() {
+    let mut sim = Simulation::new(12345, std::f64::consts::PI * 0.5, 5.0);
+    sim.update_ship_controls(0, ShipControls::new(vec![4.0], 0.0));
+    sim.run(30, None, |_| {});
+    let options = ExportOptions { ships: Some(vec![0, 4]), fields: Some(vec![Field::Heel]), ..ExportOptions::default() };
+    let table = build_table([Frame::capture(&sim, false)].iter(), &options);
+    let heel = table.names.iter().position(|name| name == "heel").unwrap();
+    // The dinghy leans under sail, and the motor boat stays upright
+    assert!(table.columns[heel][0] != 0.0 && table.columns[heel][0].is_finite());
+    assert_eq!(table.columns[heel][1], 0.0);
+  }
+}
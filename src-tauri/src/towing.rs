@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  geometry::Vec2D,
+  mooring::{LINE_DAMPING, LINE_STIFFNESS},
+  physics::{Force, ForceName, Kinematics}
+};
+
+// An impulse in kg m/s given each step like the line tensions in mooring
+pub const DEFAULT_BREAKING_STRENGTH: f64 = 5.0; // Tension a line can take before it parts, about 150 N
+
+/// A line made fast between two ships in the population, such as a tow line or a raft-up line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShipLine {
+  pub from: usize,
+  /// Where the line is made fast on the first ship, relative to its center
+  pub from_attachment: Vec2D,
+  pub to: usize,
+  /// Where the line is made fast on the second ship, relative to its center
+  pub to_attachment: Vec2D,
+  pub length: f64,
+  pub breaking_strength: f64,
+}
+impl ShipLine {
+  pub fn new(from: usize, from_attachment: Vec2D, to: usize, to_attachment: Vec2D, length: f64, breaking_strength: f64) -> Self {
+    Self { from, from_attachment, to, to_attachment, length, breaking_strength }
+  }
+
+  /// Whether the line is made fast to the given ship
+  pub fn connects(&self, index: usize) -> bool {
+    self.from == index || self.to == index
+  }
+
+  /// The tension in the line, which is zero while the line is slack
+  pub fn tension(&self, from: Kinematics, to: Kinematics) -> f64 {
+    let line = to.point(self.to_attachment) - from.point(self.from_attachment);
+    let stretch = line.magnitude() - self.length;
+    if stretch <= 0.0 {
+      return 0.0;
+    }
+    let relative_vel = to.point_velocity(self.to_attachment) - from.point_velocity(self.from_attachment);
+    let stretch_rate = relative_vel.dot(line.unit());
+    return f64::max(LINE_STIFFNESS * stretch + LINE_DAMPING * stretch_rate, 0.0);
+  }
+
+  /// The pull of the line on each ship, if it is taut
+  pub fn forces(&self, from: Kinematics, to: Kinematics) -> Option<(Force, Force)> {
+    let tension = self.tension(from, to);
+    if tension == 0.0 {
+      return None;
+    }
+    let from_loc = from.point(self.from_attachment);
+    let to_loc = to.point(self.to_attachment);
+    let pull = (to_loc - from_loc).unit().scale(tension);
+    return Some((
+      Force::new(ForceName::ShipLine, from_loc, pull),
+      Force::new(ForceName::ShipLine, to_loc, pull.scale(-1.0)),
+    ));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{ShipLine, DEFAULT_BREAKING_STRENGTH};
+  use crate::{
+    geometry::Vec2D,
+    simulation::Simulation
+  };
+
+  #[test]
+  fn test_bad_lines_are_refused() {
+    let mut sim = Simulation::new(1, 0.0, 5.0);
+    assert!(sim.tow_ship(1, 1, 10.0).is_err());
+    assert!(sim.tow_ship(1, 99, 10.0).is_err());
+    assert!(sim.tow_ship(4, 1, -1.0).is_err());
+    assert!(sim.tow_ship(4, 1, f64::NAN).is_err());
+    assert!(sim.add_line(ShipLine::new(4, Vec2D::zeros(), 1, Vec2D::zeros(), 10.0, f64::NAN)).is_err());
+    assert!(sim.get_lines().is_empty());
+
+    assert!(sim.tow_ship(4, 1, 10.0).is_ok());
+    assert_eq!(sim.get_lines().len(), 1);
+  }
+
+  #[test]
+  fn test_line_parts_above_its_breaking_strength() {
+    let mut sim = Simulation::new(1, 0.0, 5.0);
+    // A line much shorter than the distance between the ships is pulled far past its breaking strength
+    let strong = ShipLine::new(0, Vec2D::zeros(), 1, Vec2D::zeros(), 0.0, f64::MAX);
+    let weak = ShipLine::new(1, Vec2D::zeros(), 2, Vec2D::zeros(), 0.0, DEFAULT_BREAKING_STRENGTH);
+    let from = sim.get_ship(1).unwrap().kinematics();
+    let to = sim.get_ship(2).unwrap().kinematics();
+    assert!(weak.tension(from, to) > DEFAULT_BREAKING_STRENGTH);
+
+    sim.add_line(strong.clone()).unwrap();
+    sim.add_line(weak).unwrap();
+    sim.step();
+    assert_eq!(sim.get_lines(), &vec![strong]);
+  }
+}
@@ -298,21 +298,21 @@ type PrePolygon = {
 };
 export class Ship implements Drawable {
   center: XY;
-  hull: Polygon;
+  hulls: Polygon[];
   sails: Polygon[];
-  rudder: Polygon;
+  rudders: Polygon[];
 
-  constructor(center: XY, hull: PrePolygon, sails: PrePolygon[], rudder: PrePolygon, hull_color: string = 'brown', sail_color: string = 'white') {
+  constructor(center: XY, hulls: PrePolygon[], sails: PrePolygon[], rudders: PrePolygon[], hull_color: string = 'brown', sail_color: string = 'white') {
     this.center = center;
-    this.hull = new Polygon(hull.points, hull_color);
+    this.hulls = hulls.map(h => new Polygon(h.points, hull_color));
     this.sails = sails.map(s => new Polygon(s.points, sail_color));
-    this.rudder = new Polygon(rudder.points, hull_color);
+    this.rudders = rudders.map(r => new Polygon(r.points, hull_color));
   }
 
   draw(ctx: CanvasRenderingContext2D) {
     // Draw from the bottom up
-    this.rudder.draw(ctx);
-    this.hull.draw(ctx);
+    this.rudders.forEach(r => r.draw(ctx));
+    this.hulls.forEach(h => h.draw(ctx));
     this.sails.forEach(s => s.draw(ctx));
   }
 }
\ No newline at end of file
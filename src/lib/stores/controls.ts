@@ -3,9 +3,9 @@ import { writable } from "svelte/store";
 export type ControlsInterface = {
   home: () => void,
   step: () => Promise<void>,
-  play: () => void,
-  pause: () => void,
-  fastforward: () => void,
+  play: () => Promise<void>,
+  pause: () => Promise<void>,
+  fastforward: () => Promise<void>,
   toggleTracking: () => void,
   redraw: () => Promise<void>,
 };
@@ -14,9 +14,9 @@ function createInterface() {
   const { subscribe, set, update } = writable<ControlsInterface>({
     home: () => console.log("home"),
     step: async () => console.log("step"),
-    play: () => console.log("play"),
-    pause: () => console.log("pause"),
-    fastforward: () => console.log("fastforward"),
+    play: async () => console.log("play"),
+    pause: async () => console.log("pause"),
+    fastforward: async () => console.log("fastforward"),
     toggleTracking: () => console.log("toggleTracking"),
     redraw: async () => console.log("redraw"),
   });
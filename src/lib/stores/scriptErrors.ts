@@ -0,0 +1,14 @@
+import { writable } from "svelte/store";
+import type { ScriptError } from "$lib/types";
+
+function createStore() {
+  const { subscribe, set, update } = writable<ScriptError[]>([]);
+
+  return {
+    subscribe,
+    set,
+    add: (error: ScriptError) => update(errors => [...errors, error]),
+  };
+}
+
+export const scriptErrors = createStore();
@@ -0,0 +1,13 @@
+import { writable } from "svelte/store";
+import type { ShipData } from "$lib/types";
+
+function createStore() {
+  const { subscribe, set, update } = writable<ShipData | null>(null);
+
+  return {
+    subscribe,
+    set,
+  };
+}
+
+export const selectedShip = createStore();
@@ -7,12 +7,113 @@ export type Shape = {
   edges: [number, number][]
 };
 
+export type ShipControls = {
+  mainsheet_lengths: number[],
+  reef_amounts: number[],
+  traveller_positions: number[],
+  vang_tensions: number[],
+  crew_position: XY,
+  rudder_angle: number,
+  throttle: number,
+}
+
+export type EngineData = {
+  fuel: number,
+}
+
+export type Tether = {
+  kind: 'Anchor' | 'Mooring',
+  anchor_point: XY,
+  attachment: XY,
+  length: number,
+  holding_force: number | null,
+  dragging: boolean,
+}
+
 export type ShipData = {
   loc: XY,
   vel: XY,
   rot_vel: number,
   heading: number,
-  sail_angle: number,
-  mainsheet_lengths: number[],
-  rudder_angle: number,
-}
\ No newline at end of file
+  sail_angles: number[],
+  controls: ShipControls,
+  engine: EngineData | null,
+  tethers: Tether[],
+}
+
+// Payloads of events emitted by the simulation
+
+export type ShapeData = {
+  center: XY,
+  hulls: { points: XY[] }[],
+  sails: { points: XY[] }[],
+  rudders: { points: XY[] }[],
+  ghost: boolean,
+}
+
+export type PopulationUpdate = {
+  step: number,
+  count: number,
+  changed: { index: number, shape: ShapeData }[],
+  lines: { points: XY[] }[] | null,
+  marks: XY[] | null,
+}
+
+export type SelectedShipState = {
+  index: number,
+  ship: ShipData,
+}
+
+export type ScriptError = {
+  ship: number | null,
+  message: string,
+}
+
+export type SimEvent =
+  | { LineParted: { from: number, to: number } }
+  | { Collision: { a: number, b: number } }
+  | { MarkReached: { ship: number, mark: number } }
+  | { CourseFinished: { ship: number } }
+  | { ScriptError: ScriptError }
+  | "ScenarioFinished"
+
+export type ScenarioSummary = {
+  id: string,
+  name: string,
+  description: string,
+}
+
+export type ScenarioMap = {
+  width: number,
+  height: number,
+}
+
+export type RecordingInfo = {
+  recording: boolean,
+  interval: number,
+  forces: boolean,
+  frames: number,
+  first_step: number | null,
+  last_step: number | null,
+  replay: { cursor: number, playing: boolean, speed: number } | null,
+}
+
+export type SnapshotInfo = {
+  name: string | null,
+  step: number,
+}
+
+export type RemoteInfo = {
+  port: number | null,
+}
+
+export type InstrumentReadings = {
+  speed_through_water: number,
+  speed_over_ground: number,
+  compass_heading: number,
+  apparent_wind_angle: number,
+  apparent_wind_speed: number,
+  true_wind_angle: number,
+  true_wind_speed: number,
+  heel: number,
+}